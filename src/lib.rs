@@ -7,11 +7,11 @@
 extern crate chrono;
 #[macro_use]
 extern crate derive_more;
+#[cfg(test)]
 extern crate env_logger;
 #[macro_use]
 extern crate error_chain;
 extern crate itertools;
-#[cfg(test)]
 #[macro_use]
 extern crate log;
 extern crate num;
@@ -19,6 +19,8 @@ extern crate semver;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+#[cfg(feature = "serde_json")]
+extern crate serde_json;
 #[macro_use]
 extern crate shrinkwraprs;
 
@@ -30,6 +32,8 @@ extern crate strum_macros;
 mod lvm;
 /// Internal lowlevel utilities for parsing and writing LVM files
 mod lvm_format;
+/// Dynamically typed representation of LVM data
+mod value;
 
 pub use lvm::*;
 
@@ -44,11 +48,66 @@ mod errors {
           description("A deserialization error occurred")
           display("deserialization error: \"{}\"", s)
         }
+        /// A serialization error occurred
+        Serialize(s: String) {
+          description("A serialization error occurred")
+          display("serialization error: \"{}\"", s)
+        }
         /// An invalid separator
         InvalidSeparator(c: char) {
           description("An invalid separator was used by the file")
           display("An invalid separator \"{}\" was used by the file", c)
         }
+        /// The field separator is also used as the decimal separator
+        SeparatorDecimalCollision(s: Separator) {
+          description("The field separator is also used as the decimal separator")
+          display("The {} separator is also used as the decimal separator", s.as_ref())
+        }
+        /// The number of data headings does not match the number of channels
+        HeadingCountMismatch(h: usize, c: usize) {
+          description("The number of data headings does not match the number of channels")
+          display("{} data headings were found for {} channels", h, c)
+        }
+        /// A measurement header field does not hold one value for each channel
+        ChannelValueCountMismatch(k: &'static str, n: usize, c: usize) {
+          description("A measurement header field does not hold one value for each channel")
+          display("{} has {} values for {} channels", k, n, c)
+        }
+        /// A data row does not hold the number of values its measurement calls for
+        RowWidthMismatch(r: usize, n: usize, e: usize) {
+          description("A data row does not hold the number of values its measurement calls for")
+          display("Data row {} has {} values instead of {}", r, n, e)
+        }
+        /// The number of samples of a channel does not match its declared count
+        SampleCountMismatch(c: usize, d: usize, f: usize) {
+          description("The number of samples of a channel does not match its declared count")
+          display("Channel {} declares {} samples but {} were found", c, d, f)
+        }
+        /// A measurement is inconsistent
+        InvalidMeasurement(i: usize) {
+          description("A measurement is inconsistent")
+          display("Measurement {} is inconsistent", i)
+        }
+        /// A count is too large for this platform
+        CountOverflow(c: u64, t: &'static str) {
+          description("A count is too large for this platform")
+          display("The count {} does not fit in a {} on this platform", c, t)
+        }
+        /// Reading into the requested type is not supported yet
+        Unsupported(f: &'static str) {
+          description("Reading into the requested type is not supported yet")
+          display("{} is not supported yet", f)
+        }
+        /// A line is longer than the maximum allowed
+        LineTooLong(m: usize) {
+          description("A line is longer than the maximum allowed")
+          display("A line is longer than the maximum of {} bytes", m)
+        }
+        /// A number's digit grouping is misplaced
+        InvalidGrouping(t: String) {
+          description("A number's digit grouping is misplaced")
+          display("\"{}\" is not grouped in threes before the decimal separator", t)
+        }
         /// An error occurred while parsing a floating-point number
         ParseFloatError(e: std::num::ParseFloatError) {
           description("An error occurred while parsing a floating-point number")
@@ -59,6 +118,11 @@ mod errors {
           description("An error occurred parsing a line")
           display("Error parsing line {}", l)
         }
+        /// An error occurred parsing a column of a line
+        ParseColumn(c: usize) {
+          description("An error occurred parsing a column of a line")
+          display("Error parsing column {}", c)
+        }
         /// An unexpected character was found when attempting to parse a separator
         ParseSeparatorExpected(c: String, s: Separator) {
           description("An unexpected character was found when attempting to parse a separator")
@@ -89,23 +153,147 @@ mod errors {
       foreign_links {
         Io(std::io::Error);
         ParseIntError(std::num::ParseIntError);
+        ParseDateTimeError(chrono::format::ParseError);
+        ParseVersionError(semver::SemVerError);
       }
     }
 }
 
 pub use errors::*;
 
+impl Error {
+    /// Returns the 1-based line at which parsing failed, if known
+    pub fn line(&self) -> Option<usize> {
+        self.find_innermost(|kind| match *kind {
+            ErrorKind::ParseLine(l) => Some(l),
+            _ => None,
+        })
+    }
+
+    /// Returns the 1-based column at which parsing failed, if known
+    pub fn column(&self) -> Option<usize> {
+        self.find_innermost(|kind| match *kind {
+            ErrorKind::ParseColumn(c) => Some(c),
+            _ => None,
+        })
+    }
+
+    fn find_innermost<T, F: Fn(&ErrorKind) -> Option<T>>(&self, f: F) -> Option<T> {
+        let mut found = None;
+        let mut error = Some(self);
+        while let Some(e) = error {
+            found = f(e.kind()).or(found);
+            error = e.1
+                .next_error
+                .as_ref()
+                .and_then(|next| next.downcast_ref::<Error>());
+        }
+        found
+    }
+}
+
+/// A thread-safe snapshot of an `Error` and its causes
+///
+/// `Error` can hold causes that are `Send` but not `Sync`, so it does not convert into
+/// `Box<dyn std::error::Error + Send + Sync>` by itself. Converting it into a `SyncError`
+/// first, as in `.map_err(SyncError::from)?`, does.
+#[derive(Clone, Debug)]
+pub struct SyncError {
+    message: String,
+    line: Option<usize>,
+    column: Option<usize>,
+    source: Option<Box<SyncError>>,
+}
+
+impl SyncError {
+    /// Returns the 1-based line at which parsing failed, if known
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// Returns the 1-based column at which parsing failed, if known
+    pub fn column(&self) -> Option<usize> {
+        self.column
+    }
+}
+
+impl From<Error> for SyncError {
+    fn from(e: Error) -> Self {
+        let messages: Vec<String> = e.iter().map(|cause| cause.to_string()).collect();
+        let source = messages.iter().skip(1).rev().fold(None, |source, message| {
+            Some(Box::new(SyncError {
+                message: message.clone(),
+                line: None,
+                column: None,
+                source,
+            }))
+        });
+        SyncError {
+            message: messages[0].clone(),
+            line: e.line(),
+            column: e.column(),
+            source,
+        }
+    }
+}
+
+impl std::fmt::Display for SyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for SyncError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self.source {
+            Some(ref source) => {
+                let source: &(dyn std::error::Error + 'static) = &**source;
+                Some(source)
+            }
+            None => None,
+        }
+    }
+}
+
 impl serde::de::Error for Error {
     fn custom<T: std::fmt::Display>(i_message: T) -> Self {
         ErrorKind::Deserialize(i_message.to_string()).into()
     }
 }
 
-pub use lvm_format::from_reader;
+impl serde::ser::Error for Error {
+    fn custom<T: std::fmt::Display>(i_message: T) -> Self {
+        ErrorKind::Serialize(i_message.to_string()).into()
+    }
+}
+
+pub use lvm_format::{from_str, from_str_with_options, to_string, to_string_with_options,
+                     Deserializer, Precision, ReadLine, ReadOptions, Transforms, WriteOptions};
+pub use value::{to_value, Value};
+#[cfg(feature = "io")]
+pub use lvm_format::{deserialize_from, from_reader, from_reader_lossy, from_reader_with_options,
+                     from_reader_with_progress, from_reader_with_separator, to_writer,
+                     to_writer_with_options, IndexedFile, IoReader};
 
 #[cfg(test)]
 mod tests {
+    /// Parses a file, writes it back out and parses the result again,
+    /// asserting that both parses yield the same values
+    #[cfg(feature = "io")]
+    pub(crate) fn assert_roundtrip<P: AsRef<::std::path::Path>>(path: P) {
+        let path = path.as_ref();
+        let mut original = super::from_reader(::std::fs::File::open(path).unwrap()).unwrap();
+        // Comments between measurements are not written
+        original.comments.clear();
+        let text = super::to_string(&original).unwrap();
+        let reparsed = super::from_str(&text).unwrap_or_else(|e| {
+            panic!("re-parsing {:?} failed: {}\n{}", path, e, text);
+        });
+        assert_eq!(original, reparsed, "round-trip of {:?} changed its values", path);
+    }
+
     #[test]
+    #[cfg(feature = "io")]
     fn lvm_parsing() {
         ::env_logger::init();
         for de in ::std::fs::read_dir("data").unwrap() {
@@ -127,6 +315,7 @@ mod tests {
                         for measurement in lvm_file.measurements {
                             info!("{:#?}", measurement.header);
                         }
+                        assert_roundtrip(&filepath);
                     }
                     Err(e) => {
                         info!("{}", e);
@@ -5,27 +5,43 @@ use semver;
 use serde;
 use std;
 
-/// The default is the decimal separator of the system.
-///
 /// Symbol used to separate the integral part of a number from the fractional part.
+///
 /// A decimal separator usually is a dot or a comma.
-#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
-enum DecimalSeparator {
+/// Files that do not specify one are assumed to use a dot. Unknown separators are an error
+/// rather than kept, as the numbers could not be read.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[must_use]
+#[non_exhaustive]
+pub enum DecimalSeparator {
     #[serde(rename = ".")]
     /// Dot character, ASCII \0x2E
+    #[default]
     Dot,
 
     /// Comma character, ASCII \0x2C
+    ///
+    /// Dots in numbers are then read as digit grouping, as in `1.234,56`, and must split the
+    /// integral part into groups of three digits.
     #[serde(rename = ",")]
     Comma,
 }
 
+impl From<DecimalSeparator> for char {
+    fn from(s: DecimalSeparator) -> char {
+        match s {
+            DecimalSeparator::Dot => '.',
+            DecimalSeparator::Comma => ',',
+        }
+    }
+}
+
 macro_rules! wrapper_classes {
     ($($(#[$attr:meta])* pub struct $s:ident($t:ty);)*) => {
         $(
             $(#[$attr])*
             #[derive(Clone, Debug, Display, Eq, From, Into, PartialEq, PartialOrd, Shrinkwrap)]
-            #[derive(Deserialize, Serialize)]
+            #[derive(Deserialize, Hash, Serialize)]
             pub struct $s($t);
         )*
     }
@@ -53,7 +69,7 @@ wrapper_classes!(
 );
 
 /// Test numbers in a TestSeries
-#[derive(Clone, Debug, Shrinkwrap)]
+#[derive(Clone, Debug, PartialEq, Shrinkwrap)]
 pub struct TestNumbers(Vec<TestNumber>);
 
 // FIXME: Add support for comma separator too
@@ -107,20 +123,52 @@ impl<'de> serde::de::Visitor<'de> for TestNumbersVisitor {
     }
 }
 
-pub(super) type DataRow = (Vec<f64>, Option<String>);
+/// Type in which data values are stored
+///
+/// Values are `f64` by default. The `f32` feature stores them as `f32` instead, halving the
+/// memory used by large captures at the cost of precision.
+#[cfg(not(feature = "f32"))]
+pub type Sample = f64;
+
+/// Type in which data values are stored
+///
+/// Values are `f64` by default. The `f32` feature stores them as `f32` instead, halving the
+/// memory used by large captures at the cost of precision.
+#[cfg(feature = "f32")]
+pub type Sample = f32;
+
+pub(super) type DataRow = (Vec<Sample>, Option<String>);
+
+/// Converts a data value to `f64`, whichever precision it is stored in
+pub(crate) fn widen<T: Into<f64>>(value: T) -> f64 {
+    value.into()
+}
 
 /// Timezone-dependent date
 #[derive(Clone, Copy, Debug, Eq, From, Into, Ord, PartialEq, PartialOrd, Shrinkwrap)]
 #[must_use]
 pub struct Date(chrono::NaiveDate);
 
+impl Date {
+    /// Formats accepted when parsing, tried in order
+    const FORMATS: &'static [&'static str] = &["%Y/%m/%d", "%Y-%m-%d"];
+}
+
 impl std::str::FromStr for Date {
     type Err = chrono::format::ParseError;
 
+    /// Parses a date such as `2018/02/03` or `2018-02-03`
+    ///
+    /// The month and day may leave out their leading zero, as in `2018/2/3`.
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        Ok(Date(chrono::NaiveDate::parse_from_str(s, "%Y/%m/%d")?))
+        let parse = |format| chrono::NaiveDate::parse_from_str(s, format);
+        Self::FORMATS[1..]
+            .iter()
+            .fold(parse(Self::FORMATS[0]), |r, format| r.or_else(|_| parse(format)))
+            .map(Date)
     }
 }
+
 impl<'de> serde::de::Deserialize<'de> for Date {
     fn deserialize<D: serde::de::Deserializer<'de>>(
         deserializer: D,
@@ -156,17 +204,160 @@ impl<'de> serde::de::Visitor<'de> for DateVisitor {
 }
 
 /// LVM File
-#[derive(Debug, Deserialize, Serialize)]
+///
+/// LVM text is read into a `File` by `from_str`, `from_reader` or
+/// `Deserializer::deserialize_file`. Its serde `Deserialize` impl reads the fields of a file
+/// from self-describing formats, not LVM text.
+///
+/// As with `Measurement`, a file holding NaN data values is not equal to itself.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[must_use]
 pub struct File {
     /// Metadata on the file itself
     pub header: FileHeader,
     /// Measurement segments
     pub measurements: Vec<Measurement>,
+    /// Verbatim text of the file header block, when retained while reading
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_header: Option<String>,
+    /// Comment lines found between measurements, with their 1-based line numbers
+    ///
+    /// These are not written back out to LVM.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<(usize, String)>,
+}
+
+impl File {
+    /// Describes the file in a few lines of human-readable text
+    ///
+    /// Lists the date, the operator and project when known, and the channels and samples of
+    /// each measurement.
+    pub fn summary(&self) -> String {
+        use std::fmt::Write;
+        let mut summary = String::new();
+        let _ = writeln!(summary, "Date: {} {}", self.header.date, self.header.time);
+        if let Some(ref operator) = self.header.operator {
+            let _ = writeln!(summary, "Operator: {}", operator.as_str());
+        }
+        if let Some(ref project) = self.header.project {
+            let _ = writeln!(summary, "Project: {}", project.as_str());
+        }
+        let _ = writeln!(summary, "Measurements: {}", self.measurements.len());
+        let mut total = 0;
+        for (index, measurement) in self.measurements.iter().enumerate() {
+            let samples: usize = measurement.header.samples.iter().sum();
+            total += samples;
+            let _ = writeln!(
+                summary,
+                "  {}: {} channels, {} samples",
+                index + 1,
+                measurement.header.channels.0,
+                samples
+            );
+        }
+        let _ = write!(summary, "Total samples: {}", total);
+        summary
+    }
+
+    /// Sums the declared samples of every channel of every measurement
+    pub fn total_samples(&self) -> usize {
+        self.measurements
+            .iter()
+            .flat_map(|measurement| &measurement.header.samples)
+            .sum()
+    }
+
+    /// Counts the values held in the data rows of every measurement
+    ///
+    /// X-value columns are included, as are the NaN values filling out short rows.
+    pub fn total_data_points(&self) -> usize {
+        self.measurements
+            .iter()
+            .flat_map(|measurement| &measurement.data)
+            .map(|row| row.0.len())
+            .sum()
+    }
+
+    /// Writes the data of every measurement as comma-separated values
+    ///
+    /// Each measurement starts with a row of its data headings and is set apart from the one
+    /// before it by a blank line. Fails if the headings of a measurement do not match its
+    /// channels.
+    pub fn to_csv_string(&self) -> Result<String> {
+        let mut csv = String::new();
+        for (index, measurement) in self.measurements.iter().enumerate() {
+            measurement
+                .check_headings(self.header.x_columns)
+                .chain_err(|| ErrorKind::InvalidMeasurement(index + 1))?;
+            if index != 0 {
+                csv.push('\n');
+            }
+            csv.push_str(&measurement.to_csv_string(self.header.x_columns));
+        }
+        Ok(csv)
+    }
+
+    /// Runs every consistency check on the file, returning the first problem found
+    ///
+    /// The field and decimal separators must differ. Each measurement must have a data
+    /// heading and a value in every per-channel header field for each of its channels, bar
+    /// unit labels left out for its last channels, and as many full data rows as its channels
+    /// declare samples.
+    pub fn validate_all(&self) -> Result<()> {
+        self.header.check_separators()?;
+        for (index, measurement) in self.measurements.iter().enumerate() {
+            measurement
+                .validate(self.header.x_columns)
+                .chain_err(|| ErrorKind::InvalidMeasurement(index + 1))?;
+        }
+        Ok(())
+    }
+
+    /// Lists the names of the channels of every measurement, in order of first appearance
+    ///
+    /// A channel without a name in its header is named by its data heading. Names shared by
+    /// several channels are listed once.
+    pub fn channel_names(&self) -> Vec<ChannelName> {
+        let mut names: Vec<ChannelName> = vec![];
+        for measurement in &self.measurements {
+            for index in 0..measurement.header.channels.0 {
+                let channel = measurement.channel(index);
+                let name = channel
+                    .name()
+                    .filter(|name| !name.is_empty())
+                    .cloned()
+                    .or_else(|| channel.heading().map(|heading| heading.to_string().into()));
+                if let Some(name) = name {
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
+            }
+        }
+        names
+    }
+}
+
+impl IntoIterator for File {
+    type Item = Measurement;
+    type IntoIter = std::vec::IntoIter<Measurement>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.measurements.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a File {
+    type Item = &'a Measurement;
+    type IntoIter = std::slice::Iter<'a, Measurement>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.measurements.iter()
+    }
 }
 
 /// Header for the file
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[must_use]
 pub struct FileHeader {
     /// Date when the data collection started.
@@ -183,10 +374,20 @@ pub struct FileHeader {
     /// A decimal separator usually is a dot or a comma.
     ///
     /// required for version 2.0.
-    #[serde(rename = "Decimal_Separator")]
+    #[serde(default, rename = "Decimal_Separator")]
     decimal_separator: DecimalSeparator,
 
+    /// The `LabVIEW Measurement` line as read from LVM text, without its line terminator.
+    #[serde(skip)]
+    magic_line: Option<String>,
+
+    /// Separator ending the `LabVIEW Measurement` line, when read from LVM text.
+    #[serde(skip)]
+    magic_separator: Option<Separator>,
+
     /// Specifies whether each packet has a header.
+    ///
+    /// Otherwise a packet may leave out its header to reuse that of the packet before it.
     #[serde(default, rename = "Multi_Headings")]
     multi_headings: bool,
 
@@ -203,7 +404,8 @@ pub struct FileHeader {
     /// For example, a 1.0 version of a reader can parse the file until the file format changes
     /// so much that it is no longer backwards compatible.
     /// The ```Writer_Version``` supplies the actual file type version.
-    #[serde(rename = "Reader_Version")]
+    /// Files that omit this field are taken to be version 1.0.
+    #[serde(default, rename = "Reader_Version")]
     pub reader_version: Version,
 
     /// Character(s) used to separate each field in the file.
@@ -234,7 +436,9 @@ pub struct FileHeader {
     pub time_pref: TimePref,
 
     /// Version number of the file type written by the software.
-    #[serde(rename = "Writer_Version")]
+    ///
+    /// Files that omit this field are taken to be version 1.0.
+    #[serde(default, rename = "Writer_Version")]
     pub writer_version: Version,
 
     ///  Specifies which x-values are saved.
@@ -242,8 +446,74 @@ pub struct FileHeader {
     pub x_columns: XColumns,
 }
 
+impl FileHeader {
+    /// Symbol used to separate the integral part of a number from the fractional part
+    pub fn decimal_separator(&self) -> DecimalSeparator {
+        self.decimal_separator
+    }
+
+    /// Whether each packet has a header, rather than reusing that of the packet before it
+    pub fn multi_headings(&self) -> bool {
+        self.multi_headings
+    }
+
+    /// Whether this crate can parse the file, judging by the reader version it requires
+    pub fn is_compatible(&self) -> bool {
+        self.reader_version.is_supported()
+    }
+
+    /// Separator between the fields of the data following the file header
+    ///
+    /// This is the `Separator` field when the file gives one, and otherwise the separator
+    /// ending the `LabVIEW Measurement` line. Files are written with this separator throughout.
+    pub fn separator(&self) -> Separator {
+        self.separator
+    }
+
+    /// Separator ending the `LabVIEW Measurement` line, which splits the file header itself
+    ///
+    /// It may differ from the `Separator` field, which then takes over after the file header.
+    /// Headers not read from LVM text report `separator()`.
+    pub fn magic_separator(&self) -> Separator {
+        self.magic_separator.unwrap_or(self.separator)
+    }
+
+    /// The `LabVIEW Measurement` line opening the file, exactly as read
+    ///
+    /// This holds the separator ending the line, and any other characters ignored after it
+    /// when the separator was forced. Headers not read from LVM text have none.
+    pub fn magic_line(&self) -> Option<&str> {
+        self.magic_line.as_deref()
+    }
+
+    pub(crate) fn set_magic_line(&mut self, i_line: String) {
+        self.magic_line = Some(i_line);
+    }
+
+    /// Records the separators observed while reading, so that `separator()` reports the one
+    /// the data used
+    pub(crate) fn set_observed_separators(&mut self, i_magic: Separator, i_declared: bool) {
+        self.magic_separator = Some(i_magic);
+        if !i_declared {
+            self.separator = i_magic;
+        }
+    }
+
+    /// Ensures numeric fields can be told apart from the separators between them
+    pub(crate) fn check_separators(&self) -> Result<()> {
+        if char::from(self.separator) == char::from(self.decimal_separator) {
+            Err(ErrorKind::SeparatorDecimalCollision(self.separator).into())
+        } else {
+            Ok(())
+        }
+    }
+}
+
 /// A set of measurements
-#[derive(Debug, Deserialize, Serialize)]
+///
+/// Comparisons follow those of the data values, so a measurement holding NaN, such as one
+/// whose short rows were filled in, is not equal to itself or to a copy of itself.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[must_use]
 pub struct Measurement {
     /// Header for this measurement segment
@@ -251,18 +521,424 @@ pub struct Measurement {
     /// Headings for data columns
     pub data_headings: Vec<String>,
     /// Data columns
+    ///
+    /// With `XColumns::Multi`, each row holds the x-value and y-value of every channel in turn.
+    /// Values missing from the end of a row when reading, as left by channels with fewer
+    /// samples, are filled in with NaN.
     pub data: Vec<DataRow>,
+    /// Verbatim text of each data value, by row, when retained while reading
+    ///
+    /// When writing, a value is written as its text for as long as the text still reads back
+    /// as the value, so values changed since reading are formatted anew.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw_data: Vec<Vec<String>>,
+}
+
+impl Measurement {
+    const COMMENT_HEADING: &'static str = "Comment";
+
+    /// Whether the data has a trailing comment column, as announced by the data headings
+    pub fn has_comment_column(&self) -> bool {
+        self.data_headings.last().map(String::as_str) == Some(Self::COMMENT_HEADING)
+    }
+
+    /// Returns the position in `data_headings` of the heading of the channel at the specified
+    /// index
+    ///
+    /// With an x-value heading for every channel, each channel's heading follows its own.
+    fn heading_index(&self, channel: usize) -> usize {
+        let headings = self.data_headings.len() - self.has_comment_column() as usize;
+        if headings == 2 * self.header.channels.0 {
+            2 * channel + 1
+        } else {
+            channel + 1
+        }
+    }
+
+    /// Returns the y-values of the channel at the specified index
+    ///
+    /// In rows holding an x-value for every channel, each y-value follows its x-value.
+    pub fn column(&self, channel: usize) -> Option<Vec<Sample>> {
+        let channels = self.header.channels.0;
+        if channel >= channels {
+            return None;
+        }
+        self.data
+            .iter()
+            .map(|(values, _)| {
+                if values.len() == 2 * channels {
+                    values.get(2 * channel + 1)
+                } else {
+                    values
+                        .len()
+                        .checked_sub(channels)
+                        .and_then(|offset| values.get(offset + channel))
+                }.cloned()
+            })
+            .collect()
+    }
+
+    /// Returns the y-values of the channel at the specified index, de-scaled by the header
+    ///
+    /// Each value is multiplied by the channel's `y_scale` and offset by its `y0`;
+    /// missing factors leave the values unchanged.
+    pub fn scaled_column(&self, channel: usize) -> Option<Vec<f64>> {
+        let factor = |factors: &Option<Vec<f64>>| {
+            factors
+                .as_ref()
+                .and_then(|factors| factors.get(channel))
+                .cloned()
+        };
+        let scale = factor(&self.header.y_scale).unwrap_or(1.0);
+        let offset = factor(&self.header.y0).unwrap_or(0.0);
+        self.column(channel)
+            .map(|values| values.into_iter().map(|y| widen(y) * scale + offset).collect())
+    }
+
+    /// Returns the y-values of the channel with the specified name
+    ///
+    /// The name is looked up in the channel names, then in the data headings of the channels,
+    /// skipping the x-value headings. If several channels share a name, the first one is
+    /// returned.
+    pub fn column_by_name(&self, name: &str) -> Option<Vec<Sample>> {
+        self.header
+            .channel_index(name)
+            .or_else(|| {
+                (0..self.header.channels.0).find(|&channel| {
+                    self.data_headings.get(self.heading_index(channel)).map(String::as_str)
+                        == Some(name)
+                })
+            })
+            .and_then(|channel| self.column(channel))
+    }
+
+    /// Returns the waveform recorded by the channel at the specified index
+    pub fn waveform(&self, channel: usize) -> Option<Waveform> {
+        let (x0, delta_x) = self.header.time_base(channel)?;
+        Some(Waveform {
+            x0,
+            delta_x,
+            samples: *self.header.samples.get(channel)?,
+            data: self.column(channel)?,
+        })
+    }
+
+    /// Resamples the channel at the specified index to a new `delta_x` by linear interpolation
+    ///
+    /// The samples start at the channel's `x0` and cover the span of the original ones.
+    /// Returns no samples when the channel or its time base is missing, or when `new_delta_x`
+    /// is zero or runs against the channel's `delta_x`.
+    pub fn resample_channel(&self, channel: usize, new_delta_x: f64) -> Vec<f64> {
+        let (values, delta_x) = match (self.column(channel), self.header.delta_x.get(channel)) {
+            (Some(values), Some(&delta_x)) => (values, delta_x),
+            _ => return vec![],
+        };
+        let values: Vec<f64> = values.into_iter().map(widen).collect();
+        // The distance between new samples, counted in original samples
+        let step = new_delta_x / delta_x;
+        let last = match values.len().checked_sub(1) {
+            Some(last) if step.is_finite() && step > 0.0 => last,
+            _ => return vec![],
+        };
+        // Tolerate rounding in the span, so its end is not lost to it
+        let count = (last as f64 / step + 1e-9).floor() as usize + 1;
+        (0..count)
+            .map(|index| {
+                let position = index as f64 * step;
+                let before = position.floor() as usize;
+                match values.get(before + 1) {
+                    Some(&after) => {
+                        let fraction = position - before as f64;
+                        values[before] + (after - values[before]) * fraction
+                    }
+                    None => values[last],
+                }
+            })
+            .collect()
+    }
+
+    /// Iterates over the waveforms of every channel in order
+    pub fn waveforms<'a>(&'a self) -> impl Iterator<Item = Waveform> + 'a {
+        (0..self.header.channels.0).filter_map(move |channel| self.waveform(channel))
+    }
+
+    /// Ensures there is a data heading for every channel
+    ///
+    /// The x-value headings and any comment heading are not counted.
+    pub(crate) fn check_headings(&self, x_columns: XColumns) -> Result<()> {
+        let channels = self.header.channels.0;
+        let x_headings = match x_columns {
+            XColumns::Multi => channels,
+            _ => 1,
+        };
+        let headings = self.data_headings.len() - self.has_comment_column() as usize;
+        if headings.checked_sub(x_headings) == Some(channels) {
+            Ok(())
+        } else {
+            let found = headings.saturating_sub(x_headings);
+            Err(ErrorKind::HeadingCountMismatch(found, channels).into())
+        }
+    }
+
+    /// Runs the consistency checks of `File::validate_all` on this measurement
+    pub(crate) fn validate(&self, x_columns: XColumns) -> Result<()> {
+        self.check_headings(x_columns)?;
+        self.header.check_channel_values()?;
+        self.check_data(x_columns)
+    }
+
+    /// Returns the number of values in each data row
+    pub(crate) fn row_width(&self, x_columns: XColumns) -> usize {
+        let channels = self.header.channels.0;
+        match x_columns {
+            XColumns::No => channels,
+            XColumns::One => channels + 1,
+            XColumns::Multi => 2 * channels,
+        }
+    }
+
+    /// Ensures every data row holds all of its values, and every channel the declared samples
+    ///
+    /// Channels with fewer samples than the longest one leave the ends of later rows empty.
+    fn check_data(&self, x_columns: XColumns) -> Result<()> {
+        let width = self.row_width(x_columns);
+        for (row, (values, _)) in self.data.iter().enumerate() {
+            if values.len() != width {
+                return Err(ErrorKind::RowWidthMismatch(row + 1, values.len(), width).into());
+            }
+        }
+        let rows = self.data.len();
+        let longest = self.header.samples.iter().cloned().max();
+        for (channel, &samples) in self.header.samples.iter().enumerate() {
+            if samples > rows || (Some(samples) == longest && samples != rows) {
+                return Err(ErrorKind::SampleCountMismatch(channel + 1, samples, rows).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends a data row, after checking that it holds a value for every channel
+    ///
+    /// The row must be as wide as the rows already held. The first row may hold the values
+    /// of the channels alone, follow an x-value with them, or pair each with its own x-value,
+    /// as set by `FileHeader::x_columns`. A trailing comment is always allowed. The declared
+    /// sample counts are left as they are.
+    pub fn append_row(&mut self, row: DataRow) -> Result<()> {
+        let channels = self.header.channels.0;
+        let width = row.0.len();
+        let expected = match self.data.first() {
+            Some(first) => first.0.len(),
+            None if [XColumns::No, XColumns::One, XColumns::Multi]
+                .iter()
+                .any(|&x_columns| self.row_width(x_columns) == width) =>
+            {
+                width
+            }
+            None => channels + 1,
+        };
+        if width != expected {
+            let row = self.data.len() + 1;
+            return Err(ErrorKind::RowWidthMismatch(row, width, expected).into());
+        }
+        self.data.push(row);
+        Ok(())
+    }
+
+    /// Writes the data as comma-separated values, starting with a row of data headings
+    ///
+    /// Values are written with a decimal point, and fields holding commas or quotes are
+    /// quoted. Without x-value columns, the x-value heading is left out along with them.
+    pub fn to_csv_string(&self, x_columns: XColumns) -> String {
+        fn field(text: &str) -> std::borrow::Cow<'_, str> {
+            if text.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", text.replace('"', "\"\"")).into()
+            } else {
+                text.into()
+            }
+        }
+        let skip = match x_columns {
+            XColumns::No => 1,
+            XColumns::One | XColumns::Multi => 0,
+        };
+        let mut csv = self.data_headings.iter().skip(skip).map(|heading| field(heading)).join(",");
+        csv.push('\n');
+        let comments = self.has_comment_column();
+        for (values, comment) in &self.data {
+            csv.push_str(&values.iter().join(","));
+            if comments {
+                csv.push(',');
+                csv.push_str(&field(comment.as_ref().map_or("", String::as_str)));
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Consumes the measurement, returning its data rows
+    pub fn into_rows(self) -> Vec<DataRow> {
+        self.data
+    }
+
+    /// Consumes the measurement, returning its values transposed into columns
+    ///
+    /// Columns follow the order of the values in each row, starting with the x-values when
+    /// the file saves them. Comments are dropped.
+    pub fn into_columns(self) -> Vec<Vec<Sample>> {
+        let rows = self.data.len();
+        let width = self.data.iter().map(|(values, _)| values.len()).max().unwrap_or(0);
+        let mut columns: Vec<Vec<Sample>> = (0..width).map(|_| Vec::with_capacity(rows)).collect();
+        for (values, _) in self.data {
+            for (column, value) in columns.iter_mut().zip(values) {
+                column.push(value);
+            }
+        }
+        columns
+    }
+
+    /// Returns the format of the x-axis values, preferring the packet's own setting
+    /// over that of the file
+    pub fn time_pref<'a>(&'a self, file_header: &'a FileHeader) -> &'a TimePref {
+        self.header
+            .time_pref
+            .as_ref()
+            .unwrap_or(&file_header.time_pref)
+    }
+
+    /// Returns the earliest date and time at which a channel started
+    ///
+    /// Each channel's date is combined with its time. Channels lacking either are skipped, so
+    /// `None` is returned when none has both.
+    pub fn start_time(&self) -> Option<chrono::NaiveDateTime> {
+        self.header
+            .date
+            .iter()
+            .zip(&self.header.time)
+            .map(|(date, time)| date.0.and_time(time.0))
+            .min()
+    }
+
+    /// Returns the x-axis unit label of the channel at the specified index
+    ///
+    /// Returns `None` when the header has no label for the channel, or an empty one.
+    pub fn x_unit(&self, channel: usize) -> Option<&str> {
+        Self::unit(self.header.x_unit_label(channel))
+    }
+
+    /// Returns the y-axis unit label of the channel at the specified index
+    ///
+    /// Returns `None` when the header has no label for the channel, or an empty one.
+    pub fn y_unit(&self, channel: usize) -> Option<&str> {
+        Self::unit(self.header.y_unit_label(channel))
+    }
+
+    fn unit(label: Option<&Unit>) -> Option<&str> {
+        label.map(Unit::as_str).filter(|label| !label.is_empty())
+    }
+
+    /// Returns a view of the channel at the specified index
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is out of bounds.
+    pub fn channel(&self, channel: usize) -> ChannelView<'_> {
+        let channels = self.header.channels.0;
+        self.get_channel(channel).unwrap_or_else(|| {
+            panic!(
+                "channel index out of bounds: the number of channels is {} but the index is {}",
+                channels, channel
+            )
+        })
+    }
+
+    /// Returns a view of the channel at the specified index, or `None` if it is out of bounds
+    pub fn get_channel(&self, channel: usize) -> Option<ChannelView<'_>> {
+        if channel < self.header.channels.0 {
+            Some(ChannelView {
+                measurement: self,
+                channel,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// A view of a single channel of a measurement
+#[derive(Clone, Copy, Debug)]
+#[must_use]
+pub struct ChannelView<'a> {
+    measurement: &'a Measurement,
+    channel: usize,
+}
+
+impl<'a> ChannelView<'a> {
+    /// Index of the channel within its measurement
+    pub fn index(&self) -> usize {
+        self.channel
+    }
+
+    /// Name of the channel, if the header names it
+    pub fn name(&self) -> Option<&'a ChannelName> {
+        self.measurement.header.channels.1.get(self.channel)
+    }
+
+    /// Heading of the channel's data column
+    pub fn heading(&self) -> Option<&'a str> {
+        self.measurement
+            .data_headings
+            .get(self.measurement.heading_index(self.channel))
+            .map(String::as_str)
+    }
+
+    /// Unit type of the channel's y-values
+    pub fn y_dimension(&self) -> Option<&'a UnitType> {
+        self.measurement.header.y_dimension.get(self.channel)
+    }
+
+    /// The y-values of the channel
+    pub fn data(&self) -> Option<Vec<Sample>> {
+        self.measurement.column(self.channel)
+    }
+
+    /// The waveform recorded by the channel
+    pub fn waveform(&self) -> Option<Waveform> {
+        self.measurement.waveform(self.channel)
+    }
+}
+
+/// A channel's samples along with its x-axis description
+#[derive(Clone, Debug, PartialEq)]
+#[must_use]
+pub struct Waveform {
+    /// The initial value for the x-axis
+    pub x0: f64,
+    /// The increment between points on the x-axis
+    pub delta_x: f64,
+    /// Number of samples declared by the header
+    pub samples: usize,
+    /// The y-values
+    pub data: Vec<Sample>,
+}
+
+impl Waveform {
+    /// Generates the x-value of each sample, `x0 + index * delta_x`
+    ///
+    /// `delta_x` may be negative or zero, making the x-values fall or stay put.
+    pub fn x_values(&self) -> impl Iterator<Item = f64> + '_ {
+        (0..self.data.len()).map(move |index| self.x0 + index as f64 * self.delta_x)
+    }
 }
 
 /// Header for measurement data
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[must_use]
 pub struct MeasurementHeader {
     /// Number of channels in the packet.
     ///
-    /// This field must occur before any fields that depend on it.
-    /// For example, the Samples field has entries for each channel,
-    /// so the reader must know the number of channels to properly parse it.
+    /// The format calls for this field to occur before any fields that depend on it.
+    /// For example, the Samples field has entries for each channel.
+    /// It is written first, but may be read from anywhere in the packet header,
+    /// as per-channel values are only matched up with the channels once the header is read.
     #[serde(rename = "Channels")]
     pub channels: (usize, Vec<ChannelName>),
 
@@ -278,8 +954,17 @@ pub struct MeasurementHeader {
     /// The .lvm format assumes all data is equally spaced in the x-dimension.
     /// There is one value for each data set in the packet.
     /// The value appears in the same column as the y-values for the data.
+    /// A negative increment runs the x-axis backwards, as in reversed-time captures, while
+    /// a zero increment gives every sample the same x-value, as in event-indexed captures.
     #[serde(rename = "Delta_X")]
-    pub delta_x: Vec<f32>,
+    pub delta_x: Vec<f64>,
+
+    /// The interval between acquisitions of the data sets in the segment.
+    ///
+    /// There is one value for each data set in the packet.
+    /// The value appears in the same column as the y-values for the data.
+    #[serde(rename = "Interval")]
+    pub interval: Option<Vec<f64>>,
 
     /// Comments the user adds to the segment header.
     ///
@@ -288,8 +973,17 @@ pub struct MeasurementHeader {
     #[serde(rename = "Notes")]
     pub notes: Option<String>,
 
+    /// Notes given for each channel, placed in the same column as the y data of the channel.
+    ///
+    /// Filled in when reading a packet whose Notes line holds more than one value; `notes`
+    /// keeps the line as written. Channels without a note are `None`. This field is not written.
+    #[serde(skip)]
+    pub notes_per_channel: Vec<Option<String>>,
+
     /// Number of samples in each waveform in the packet.
-    #[serde(rename = "Samples")]
+    ///
+    /// A single value in the file applies to every channel.
+    #[serde(deserialize_with = "deserialize_counts", rename = "Samples")]
     pub samples: Vec<usize>,
 
     /// Name of the test that acquired the segment of data.
@@ -311,8 +1005,14 @@ pub struct MeasurementHeader {
     #[serde(rename = "Time")]
     pub time: Vec<Time>,
 
+    /// Format of the x-axis values of this packet, overriding the file's ```Time_Pref```.
+    #[serde(rename = "Time_Pref")]
+    pub time_pref: Option<TimePref>,
+
     /// Model number of the unit under test.
-    #[serde(rename = "UUT_M/N")]
+    ///
+    /// DIAdem writes the key as ```UUT_MN```.
+    #[serde(alias = "UUT_MN", rename = "UUT_M/N")]
     pub uut_mn: Option<ModelNumber>,
 
     /// Name or instrument class of the unit under test.
@@ -320,7 +1020,9 @@ pub struct MeasurementHeader {
     pub uut_name: Option<InstrumentName>,
 
     /// Serial number of the unit under test.
-    #[serde(rename = "UUT_S/N")]
+    ///
+    /// DIAdem writes the key as ```UUT_SN```.
+    #[serde(alias = "UUT_SN", rename = "UUT_S/N")]
     pub uut_sn: Option<SerialNumber>,
 
     /// The initial value for the x-axis.
@@ -328,7 +1030,7 @@ pub struct MeasurementHeader {
     /// Each data set in the packet has a single X0 value.
     /// The value appears in the same column as the y-values for the data.
     #[serde(rename = "X0")]
-    pub x0: Vec<f32>,
+    pub x0: Vec<f64>,
 
     /// Unit type of the x-axis.
     ///
@@ -344,12 +1046,27 @@ pub struct MeasurementHeader {
     #[serde(rename = "X_Unit_Label")]
     pub x_unit_label: Option<Vec<Unit>>,
 
+    /// Offset added to the scaled y-values.
+    ///
+    /// Not part of the published format, but written by some waveform exports.
+    /// There is one value for each data set in the packet.
+    #[serde(rename = "Y0")]
+    pub y0: Option<Vec<f64>>,
+
     /// Unit type of the y-axis.
     ///
     /// The actual data does not need to be in SI units.
     /// The ```Y_Unit_Label``` field indicates the actual units of the data.
+    /// There is one value for each channel, defaulting to ```Electric_Potential```.
     #[serde(default, rename = "Y_Dimension")]
-    pub y_dimension: UnitType,
+    pub y_dimension: Vec<UnitType>,
+
+    /// Factor by which the raw y-values are multiplied.
+    ///
+    /// Not part of the published format, but written by some waveform exports.
+    /// There is one value for each data set in the packet.
+    #[serde(rename = "Y_Scale")]
+    pub y_scale: Option<Vec<f64>>,
 
     /// Labels for the units used in plotting the y data.
     ///
@@ -359,9 +1076,154 @@ pub struct MeasurementHeader {
     pub y_unit_label: Option<Vec<Unit>>,
 }
 
+/// Deserializes counts as `u64`, reporting those that do not fit in a `usize`
+fn deserialize_counts<'de, D: serde::de::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<usize>, D::Error> {
+    use serde::de::Deserialize;
+    Vec::<u64>::deserialize(deserializer)?
+        .into_iter()
+        .map(|count| narrow_count(count).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+/// Converts a count to a narrower integer type, failing if it does not fit
+pub(crate) fn narrow_count<T: std::convert::TryFrom<u64>>(count: u64) -> Result<T> {
+    T::try_from(count)
+        .map_err(|_| ErrorKind::CountOverflow(count, std::any::type_name::<T>()).into())
+}
+
+impl MeasurementHeader {
+    /// Returns the sample rate of the channel at the specified index, derived from `delta_x`
+    ///
+    /// The rate is negative when `delta_x` is. Returns `None` when the channel has no
+    /// `delta_x` or it is zero.
+    pub fn sample_rate(&self, channel: usize) -> Option<f64> {
+        match self.delta_x.get(channel) {
+            Some(&delta_x) if delta_x != 0.0 => Some(1.0 / delta_x),
+            _ => None,
+        }
+    }
+
+    /// Returns the index of the first channel with the specified name
+    ///
+    /// Names are compared exactly, so the lookup is case-sensitive.
+    pub fn channel_index(&self, name: &str) -> Option<usize> {
+        self.channels
+            .1
+            .iter()
+            .position(|channel| channel.as_str() == name)
+    }
+
+    /// Returns the x-axis unit label of the channel at the specified index
+    ///
+    /// Returns `None` when the labels stop short of the channel or are absent altogether.
+    pub fn x_unit_label(&self, channel: usize) -> Option<&Unit> {
+        self.x_unit_label.as_ref()?.get(channel)
+    }
+
+    /// Returns the y-axis unit label of the channel at the specified index
+    ///
+    /// Returns `None` when the labels stop short of the channel or are absent altogether.
+    pub fn y_unit_label(&self, channel: usize) -> Option<&Unit> {
+        self.y_unit_label.as_ref()?.get(channel)
+    }
+
+    /// Returns the `(x0, delta_x)` of the channel at the specified index
+    pub fn time_base(&self, channel: usize) -> Option<(f64, f64)> {
+        Some((*self.x0.get(channel)?, *self.delta_x.get(channel)?))
+    }
+
+    /// Ensures every per-channel field present holds one value for each channel
+    ///
+    /// Unit labels need not be filled in for all channels, so may hold fewer values.
+    pub(crate) fn check_channel_values(&self) -> Result<()> {
+        let channels = self.channels.0;
+        let counts = [
+            ("Samples", Some(self.samples.len()), false),
+            ("Date", Some(self.date.len()), false),
+            ("Time", Some(self.time.len()), false),
+            ("X0", Some(self.x0.len()), false),
+            ("Delta_X", Some(self.delta_x.len()), false),
+            ("Y_Dimension", Some(self.y_dimension.len()), false),
+            ("Interval", self.interval.as_ref().map(Vec::len), false),
+            ("X_Dimension", self.x_dimension.as_ref().map(Vec::len), false),
+            ("X_Unit_Label", self.x_unit_label.as_ref().map(Vec::len), true),
+            ("Y0", self.y0.as_ref().map(Vec::len), false),
+            ("Y_Scale", self.y_scale.as_ref().map(Vec::len), false),
+            ("Y_Unit_Label", self.y_unit_label.as_ref().map(Vec::len), true),
+        ];
+        for &(key, count, partial) in &counts {
+            match count {
+                Some(count) if count > channels || (count < channels && !partial) => {
+                    return Err(ErrorKind::ChannelValueCountMismatch(key, count, channels).into())
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills in per-channel values that the file left unspecified or combined with others
+    pub(crate) fn apply_defaults(&mut self) {
+        if let Some(ref mut x_dimension) = self.x_dimension {
+            Self::split_units(x_dimension, &mut self.x_unit_label);
+        }
+        Self::split_units(&mut self.y_dimension, &mut self.y_unit_label);
+        if self.y_dimension.is_empty() {
+            self.y_dimension = vec![UnitType::default(); self.channels.0];
+        }
+        // Some writers give a single sample count shared by all channels
+        if self.samples.len() == 1 && self.channels.0 > 1 {
+            self.samples = vec![self.samples[0]; self.channels.0];
+        }
+    }
+
+    /// Spreads a Notes line holding several separated values across the channels
+    pub(crate) fn split_notes(&mut self, i_separator: char) {
+        let notes = match self.notes {
+            Some(ref notes) => notes,
+            None => return,
+        };
+        // A single trailing separator ends the line like on other header lines
+        let notes = notes.strip_suffix(i_separator).unwrap_or(notes);
+        if !notes.contains(i_separator) {
+            return;
+        }
+        self.notes_per_channel = notes
+            .split(i_separator)
+            .map(|note| match note.trim() {
+                "" => None,
+                note => Some(note.to_string()),
+            })
+            .collect();
+    }
+
+    /// Moves unit labels embedded in unit types into the matching unit labels
+    ///
+    /// Explicit unit labels take precedence over embedded ones.
+    fn split_units(unit_types: &mut [UnitType], unit_labels: &mut Option<Vec<Unit>>) {
+        for (index, unit_type) in unit_types.iter_mut().enumerate() {
+            if let Some((split_type, split_unit)) = unit_type.split_unit() {
+                *unit_type = split_type;
+                let labels = unit_labels.get_or_insert_with(Vec::new);
+                if labels.len() <= index {
+                    labels.resize(index + 1, Unit::Other(String::new()));
+                }
+                if labels[index] == Unit::Other(String::new()) {
+                    labels[index] = split_unit;
+                }
+            }
+        }
+    }
+}
+
 /// Character(s) used to separate each field in the file
-#[derive(AsRefStr, Clone, Copy, Debug, Deserialize, Serialize)]
+///
+/// Unknown separators are an error rather than kept, as the fields could not be split.
+#[derive(AsRefStr, Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 #[must_use]
+#[non_exhaustive]
 pub enum Separator {
     /// Comma separator (ASCII \0x2C)
     Comma,
@@ -370,7 +1232,67 @@ pub enum Separator {
 }
 
 impl Separator {
-    pub(crate) fn try_from(i_char: char) -> Result<Separator> {
+    /// Looks up a separator by the name used in the header, such as `Tab`
+    pub(crate) fn from_name(i_name: &str) -> Option<Separator> {
+        [Separator::Comma, Separator::Tab]
+            .iter()
+            .cloned()
+            .find(|separator| separator.as_ref() == i_name)
+    }
+
+    const OPTIONS: &'static [&'static str] = &["Comma", "Tab", ",", "\t"];
+
+    /// Looks up a separator written as an escape sequence, such as `\t` or the ASCII code
+    /// `\0x2C`
+    ///
+    /// The code may also be written as `\x2C` or `0x2C`, in either case.
+    pub(crate) fn from_escape(i_escape: &str) -> Option<Separator> {
+        if i_escape == "\\t" {
+            return Some(Separator::Tab);
+        }
+        let code = i_escape.trim_start_matches('\\');
+        let code = if code.starts_with('0') && code.len() > 2 {
+            &code[1..]
+        } else {
+            code
+        };
+        if !code.starts_with('x') && !code.starts_with('X') {
+            return None;
+        }
+        match u8::from_str_radix(&code[1..], 16).map(char::from) {
+            Ok(',') => Some(Separator::Comma),
+            Ok('\t') => Some(Separator::Tab),
+            _ => None,
+        }
+    }
+
+    /// Guesses the separator of a line as the candidate occurring in it most often
+    ///
+    /// Tabs win ties with commas. Semicolons also separate the fields of some files, which
+    /// then use commas as decimal separators. A line is taken to be split on semicolons when
+    /// they outnumber tabs and no field between them holds more than one comma. As semicolons
+    /// are not supported, `None` is then returned, as it is for a line holding no candidate.
+    pub fn detect_from_line(i_line: &str) -> Option<Separator> {
+        let count = |text: &str, c| text.matches(c).count();
+        let tabs = count(i_line, '\t');
+        let commas = count(i_line, ',');
+        let semicolons = count(i_line, ';');
+        let split_on_semicolons =
+            semicolons > tabs && i_line.split(';').all(|field| count(field, ',') <= 1);
+        if split_on_semicolons || tabs == 0 && commas == 0 {
+            None
+        } else if tabs >= commas {
+            Some(Separator::Tab)
+        } else {
+            Some(Separator::Comma)
+        }
+    }
+}
+
+impl std::convert::TryFrom<char> for Separator {
+    type Error = Error;
+
+    fn try_from(i_char: char) -> Result<Separator> {
         match i_char {
             ',' => Ok(Separator::Comma),
             '\t' => Ok(Separator::Tab),
@@ -379,6 +1301,23 @@ impl Separator {
     }
 }
 
+/// Accepts the separator character itself, its name, ignoring case, or an escape sequence
+impl<'a> std::convert::TryFrom<&'a str> for Separator {
+    type Error = Error;
+
+    fn try_from(i_str: &'a str) -> Result<Separator> {
+        match i_str {
+            "," => Ok(Separator::Comma),
+            "\t" => Ok(Separator::Tab),
+            s if s.eq_ignore_ascii_case("comma") => Ok(Separator::Comma),
+            s if s.eq_ignore_ascii_case("tab") => Ok(Separator::Tab),
+            s => Separator::from_escape(s).ok_or_else(|| {
+                ErrorKind::ParseTokenUnexpected(s.to_string(), Self::OPTIONS).into()
+            }),
+        }
+    }
+}
+
 impl From<Separator> for char {
     fn from(s: Separator) -> char {
         match s {
@@ -405,11 +1344,30 @@ impl std::fmt::Display for Time {
     }
 }
 
+impl Time {
+    /// Formats accepted when parsing, tried in order
+    const FORMATS: &'static [&'static str] = &[
+        "%H:%M:%S%.f",
+        "%H:%M:%S",
+        "%H:%M",
+        "%I:%M:%S%.f %p",
+        "%I:%M %p",
+    ];
+}
+
 impl std::str::FromStr for Time {
     type Err = chrono::format::ParseError;
 
+    /// Parses a time such as `12:34:56.789`, `12:34:56` or `12:34`
+    ///
+    /// Times on a 12-hour clock followed by `AM` or `PM`, such as `02:30:00 PM`, are accepted
+    /// as well.
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        Ok(Time(chrono::NaiveTime::parse_from_str(s, "%H:%M:%S%.f")?))
+        let parse = |format| chrono::NaiveTime::parse_from_str(s, format);
+        Self::FORMATS[1..]
+            .iter()
+            .fold(parse(Self::FORMATS[0]), |r, format| r.or_else(|_| parse(format)))
+            .map(Time)
     }
 }
 
@@ -444,14 +1402,28 @@ impl<'de> serde::de::Visitor<'de> for TimeVisitor {
 }
 
 /// Format of axis values - absolute or relative
-#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 #[must_use]
+#[non_exhaustive]
 pub enum TimePref {
     /// x-value is number of seconds since midnight, January 1, 1904 GMT
     Absolute,
 
     /// x-value is number of seconds since the date and time stamps
     Relative,
+
+    /// A format not known to this library, kept verbatim
+    Other(String),
+}
+
+impl TimePref {
+    fn as_str(&self) -> &str {
+        match *self {
+            TimePref::Absolute => "Absolute",
+            TimePref::Relative => "Relative",
+            TimePref::Other(ref s) => s,
+        }
+    }
 }
 
 impl Default for TimePref {
@@ -460,27 +1432,163 @@ impl Default for TimePref {
     }
 }
 
+impl std::fmt::Display for TimePref {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for TimePref {
+    type Err = std::string::ParseError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "Absolute" => TimePref::Absolute,
+            "Relative" => TimePref::Relative,
+            s => TimePref::Other(s.to_string()),
+        })
+    }
+}
+
+impl<'de> serde::de::Deserialize<'de> for TimePref {
+    fn deserialize<D: serde::de::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_str(TimePrefVisitor)
+    }
+}
+
+impl serde::ser::Serialize for TimePref {
+    fn serialize<S: serde::ser::Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+        s.serialize_str(self.as_str())
+    }
+}
+
+#[must_use]
+struct TimePrefVisitor;
+
+impl<'de> serde::de::Visitor<'de> for TimePrefVisitor {
+    type Value = TimePref;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("A time preference such as Absolute or Relative")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, value: &str) -> std::result::Result<Self::Value, E> {
+        use std::str::FromStr;
+        Self::Value::from_str(value).map_err(serde::de::Error::custom)
+    }
+}
+
 /// Label for an axis
-//FIXME: Should probable be an "arbitrary text string"
-#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 #[must_use]
+#[non_exhaustive]
 pub enum Unit {
     /// Milliamps
     Milliamps,
     /// Volts
     Volts,
+    /// Any other label, kept verbatim
+    Other(String),
+}
+
+impl Unit {
+    fn as_str(&self) -> &str {
+        match *self {
+            Unit::Milliamps => "Milliamps",
+            Unit::Volts => "Volts",
+            Unit::Other(ref s) => s,
+        }
+    }
+}
+
+impl std::fmt::Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Unit {
+    type Err = std::string::ParseError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "Milliamps" => Unit::Milliamps,
+            "Volts" => Unit::Volts,
+            s => Unit::Other(s.to_string()),
+        })
+    }
+}
+
+impl<'de> serde::de::Deserialize<'de> for Unit {
+    fn deserialize<D: serde::de::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_str(UnitVisitor)
+    }
+}
+
+impl serde::ser::Serialize for Unit {
+    fn serialize<S: serde::ser::Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+        s.serialize_str(self.as_str())
+    }
+}
+
+#[must_use]
+struct UnitVisitor;
+
+impl<'de> serde::de::Visitor<'de> for UnitVisitor {
+    type Value = Unit;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("A unit label such as Volts")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, value: &str) -> std::result::Result<Self::Value, E> {
+        use std::str::FromStr;
+        Self::Value::from_str(value).map_err(serde::de::Error::custom)
+    }
 }
 
 /// Specifies the unit type of an axis
-#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 #[must_use]
+#[non_exhaustive]
 pub enum UnitType {
     /// Electric Potential (Jouls)
-    #[serde(rename = "Electric_Potential")]
     ElectricPotential,
 
     /// Time (seconds)
     Time,
+
+    /// A unit type not known to this library, kept verbatim
+    Other(String),
+}
+
+impl UnitType {
+    fn as_str(&self) -> &str {
+        match *self {
+            UnitType::ElectricPotential => "Electric_Potential",
+            UnitType::Time => "Time",
+            UnitType::Other(ref s) => s,
+        }
+    }
+
+    /// Splits a unit type written with an embedded unit label, such as `Time (s)`
+    fn split_unit(&self) -> Option<(UnitType, Unit)> {
+        let s = match *self {
+            UnitType::Other(ref s) => s.trim_end(),
+            _ => return None,
+        };
+        if !s.ends_with(')') {
+            return None;
+        }
+        let open = s.rfind('(')?;
+        let unit_type = s[..open].trim_end().parse().ok()?;
+        let unit = s[open + 1..s.len() - 1].trim().parse().ok()?;
+        Some((unit_type, unit))
+    }
 }
 
 impl Default for UnitType {
@@ -489,6 +1597,57 @@ impl Default for UnitType {
     }
 }
 
+impl std::fmt::Display for UnitType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for UnitType {
+    type Err = std::string::ParseError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "Electric_Potential" => UnitType::ElectricPotential,
+            "Time" => UnitType::Time,
+            s => UnitType::Other(s.to_string()),
+        })
+    }
+}
+
+impl<'de> serde::de::Deserialize<'de> for UnitType {
+    fn deserialize<D: serde::de::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_str(UnitTypeVisitor)
+    }
+}
+
+impl serde::ser::Serialize for UnitType {
+    fn serialize<S: serde::ser::Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+        s.serialize_str(self.as_str())
+    }
+}
+
+#[must_use]
+struct UnitTypeVisitor;
+
+impl<'de> serde::de::Visitor<'de> for UnitTypeVisitor {
+    type Value = UnitType;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("A unit type such as Electric_Potential or Time")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, value: &str) -> std::result::Result<Self::Value, E> {
+        use std::str::FromStr;
+        Self::Value::from_str(value).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Newest file format version, as `(major, minor)`, that this crate can read
+pub const SUPPORTED_VERSION: (u64, u64) = (2, 0);
+
 /// Reader / writer version
 #[derive(Clone, Debug, Eq, From, Into, Ord, PartialEq, PartialOrd, Shrinkwrap)]
 #[must_use]
@@ -504,14 +1663,40 @@ impl std::fmt::Display for Version {
     }
 }
 
+impl Default for Version {
+    fn default() -> Self {
+        Version(semver::Version::new(1, 0, 0))
+    }
+}
+
+impl Version {
+    /// Whether this version is no newer than `SUPPORTED_VERSION`
+    pub(crate) fn is_supported(&self) -> bool {
+        (self.0.major, self.0.minor) <= SUPPORTED_VERSION
+    }
+
+    /// Whether this version is the same as or newer than `other`, such as `"2.0"` or `"1"`
+    ///
+    /// Versions are written as in LVM files, with missing components taken as zero. A version
+    /// that does not parse is never reached, so `false` is returned for it.
+    pub fn at_least(&self, other: &str) -> bool {
+        other.parse::<Version>().is_ok_and(|other| *self >= other)
+    }
+
+    /// Whether this is a version 2 format, the latest major version
+    pub fn is_v2(&self) -> bool {
+        self.0.major == 2
+    }
+}
+
 impl std::str::FromStr for Version {
     type Err = semver::SemVerError;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        if !s.contains('.') {
-            semver::Version::parse(&format!("{}.0.0", s)).map(Version)
-        } else {
-            semver::Version::parse(s).map(Version)
+        match s.matches('.').count() {
+            0 => semver::Version::parse(&format!("{}.0.0", s)).map(Version),
+            1 => semver::Version::parse(&format!("{}.0", s)).map(Version),
+            _ => semver::Version::parse(s).map(Version),
         }
     }
 }
@@ -547,8 +1732,12 @@ impl<'de> serde::de::Visitor<'de> for VersionVisitor {
 }
 
 ///  Specifies which x-values are saved.
-#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+///
+/// Unknown values are an error rather than kept, as the layout of the data rows depends on
+/// them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
 #[must_use]
+#[non_exhaustive]
 pub enum XColumns {
     /// Save no x-values.
     ///
@@ -561,6 +1750,8 @@ pub enum XColumns {
     One,
 
     /// Saves a column of x data for every column of y data.
+    ///
+    /// Each x column precedes the y column it belongs to.
     Multi,
 }
 
@@ -569,3 +1760,48 @@ impl Default for XColumns {
         XColumns::One
     }
 }
+
+impl XColumns {
+    const OPTIONS: &'static [&'static str] = &["No", "One", "Multi", "0", "1", "2"];
+}
+
+impl std::str::FromStr for XColumns {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "No" | "0" => Ok(XColumns::No),
+            "One" | "1" => Ok(XColumns::One),
+            "Multi" | "2" => Ok(XColumns::Multi),
+            _ => Err(ErrorKind::ParseTokenUnexpected(s.to_string(), Self::OPTIONS).into()),
+        }
+    }
+}
+
+impl<'de> serde::de::Deserialize<'de> for XColumns {
+    fn deserialize<D: serde::de::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_str(XColumnsVisitor)
+    }
+}
+
+#[must_use]
+struct XColumnsVisitor;
+
+impl<'de> serde::de::Visitor<'de> for XColumnsVisitor {
+    type Value = XColumns;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("One of No, One, Multi or the numeric codes 0, 1, 2")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, value: &str) -> std::result::Result<Self::Value, E> {
+        use std::str::FromStr;
+        Self::Value::from_str(value).map_err(serde::de::Error::custom)
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, value: u64) -> std::result::Result<Self::Value, E> {
+        self.visit_str(&value.to_string())
+    }
+}
@@ -5,38 +5,268 @@ use serde;
 use std;
 
 use serde::de::IntoDeserializer;
+use std::borrow::Cow;
+use std::convert::TryFrom;
 
+/// Character enclosing string values that contain the separator
+///
+/// A quote inside a quoted value is escaped by doubling it.
+const QUOTE: char = '"';
+/// Keys whose values are free text that may contain the separator
+const TEXT_KEYS: &[&str] = &["Description", "Notes", "Operator", "Project"];
+/// Header fields with a default value, as written, that readers assume when the field is absent
+const DEFAULT_FIELDS: &[(&str, &str)] = &[
+    ("Decimal_Separator", "."),
+    ("Multi_Headings", "No"),
+    ("Separator", "Tab"),
+    ("X_Columns", "One"),
+];
+
+/// Rewrites a number written with the specified decimal separator for `str::parse`
+///
+/// When the decimal separator is a comma, dots are taken as digit grouping and dropped. They
+/// must then split the integral part into groups of three digits, so that `1.5` is rejected
+/// rather than read as `15`.
+fn normalize_real(i_token: &str, i_decimal_separator: char) -> Result<Cow<'_, str>> {
+    if i_decimal_separator == '.' {
+        return Ok(Cow::from(i_token));
+    }
+    let integral_end = i_token
+        .find(&[i_decimal_separator, 'e', 'E'][..])
+        .unwrap_or(i_token.len());
+    let (integral, rest) = i_token.split_at(integral_end);
+    let digits = integral.trim_start_matches(&['+', '-'][..]);
+    let mut groups = digits.split('.');
+    let leading = groups.next().unwrap_or_default();
+    let grouped = !digits.contains('.')
+        || (!leading.is_empty() && leading.len() <= 3 && groups.all(|group| group.len() == 3));
+    if !grouped || rest.contains('.') {
+        return Err(ErrorKind::InvalidGrouping(i_token.to_string()).into());
+    }
+    Ok(Cow::from(i_token.replace('.', "").replace(i_decimal_separator, ".")))
+}
+
+/// A source of lines of text
+pub trait ReadLine {
+    /// Appends the next line, including its terminator, returning the number of bytes read
+    fn read_line(&mut self, o_line: &mut String) -> Result<usize>;
+
+    /// Appends the next line like `read_line`, but may stop once `i_limit` bytes are read
+    ///
+    /// Sources that buffer input should not read past the limit. By default the whole line
+    /// is read.
+    fn read_line_max(&mut self, o_line: &mut String, i_limit: usize) -> Result<usize> {
+        let _ = i_limit;
+        self.read_line(o_line)
+    }
+}
+
+impl ReadLine for &str {
+    fn read_line(&mut self, o_line: &mut String) -> Result<usize> {
+        let end = self.find('\n').map_or(self.len(), |index| index + 1);
+        o_line.push_str(&self[..end]);
+        *self = &self[end..];
+        Ok(end)
+    }
+}
+
+/// Lines of text read from a `std::io::BufRead`
+#[cfg(feature = "io")]
+#[derive(Debug)]
+pub struct IoReader<R: std::io::BufRead>(R);
+
+#[cfg(feature = "io")]
+impl<R: std::io::BufRead> ReadLine for IoReader<R> {
+    fn read_line(&mut self, o_line: &mut String) -> Result<usize> {
+        Ok(self.0.read_line(o_line)?)
+    }
+
+    fn read_line_max(&mut self, o_line: &mut String, i_limit: usize) -> Result<usize> {
+        use std::io::{BufRead, Read};
+        let mut bytes = vec![];
+        let count = (&mut self.0).take(i_limit as u64).read_until(b'\n', &mut bytes)?;
+        match String::from_utf8(bytes) {
+            Ok(line) => o_line.push_str(&line),
+            // The limit may split a character, but the line is then too long anyway
+            Err(e) if count == i_limit => o_line.push_str(&String::from_utf8_lossy(e.as_bytes())),
+            Err(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                ).into())
+            }
+        }
+        Ok(count)
+    }
+}
+
+/// A sink for text
+trait WriteText {
+    /// Writes formatted text
+    fn write_fmt(&mut self, i_args: std::fmt::Arguments) -> Result<()>;
+}
+
+impl WriteText for String {
+    fn write_fmt(&mut self, i_args: std::fmt::Arguments) -> Result<()> {
+        std::fmt::Write::write_fmt(self, i_args).map_err(serde::ser::Error::custom)
+    }
+}
+
+#[cfg(feature = "io")]
+#[derive(Debug)]
+struct IoWriter<W: std::io::Write>(W);
+
+#[cfg(feature = "io")]
+impl<W: std::io::Write> WriteText for IoWriter<W> {
+    fn write_fmt(&mut self, i_args: std::fmt::Arguments) -> Result<()> {
+        Ok(self.0.write_fmt(i_args)?)
+    }
+}
+
+/// Reads LVM file data line by line
+///
+/// Most users want `from_reader` or `from_str`, which parse a whole file.
 #[derive(Debug)]
 #[must_use]
-struct Deserializer<R: std::io::BufRead> {
+pub struct Deserializer<R: ReadLine> {
+    column_major: bool,
+    comments: Vec<(usize, String)>,
+    decimal_separator: char,
+    diagnostics: Option<Vec<(usize, Error)>>,
     line_current: String,
     line_current_pos: usize,
     line_index: usize,
-    input: std::io::Lines<R>,
+    /// Byte offset of the start of the current line
+    line_offset: u64,
+    input: R,
+    lenient: bool,
+    line_pending: Option<String>,
+    magic_line: Option<String>,
+    max_line_length: Option<usize>,
+    /// Bytes read from the input so far
+    offset: u64,
+    /// Where the data of each packet starts, recorded instead of parsing the data
+    #[cfg(feature = "io")]
+    packet_offsets: Option<Vec<DataOffset>>,
+    raw: Option<String>,
+    raw_data: bool,
     separator: char,
+    separator_declared: Option<char>,
+    separator_forced: bool,
+    separator_key: bool,
     sequence_style: SequenceStyle,
+    struct_fields: &'static [&'static str],
+    text_key: bool,
+    transform_key: Option<String>,
+    transforms: Transforms,
 }
 
-impl<R: std::io::BufRead> Deserializer<R> {
-    const BOOL_YES: &'static str = "Yes";
-    const BOOL_NO: &'static str = "No";
-    const BOOL_OPTIONS: &'static [&'static str] = &[Self::BOOL_NO, Self::BOOL_YES];
+impl<R: ReadLine> Deserializer<R> {
+    /// Spellings of `true`, matched ignoring case, of which `Yes` is the one written
+    const BOOL_TRUE: &'static [&'static str] = &["Yes", "True", "1"];
+    /// Spellings of `false`, matched ignoring case, of which `No` is the one written
+    const BOOL_FALSE: &'static [&'static str] = &["No", "False", "0"];
+    const BOOL_OPTIONS: &'static [&'static str] = &["No", "Yes", "False", "True", "0", "1"];
+    const END_OF_HEADER: &'static str = "***End_of_Header***";
     const HEADER: &'static str = "LabVIEW Measurement";
     const HEADER_OPTIONS: &'static [&'static str] = &[Self::HEADER];
+    const SEPARATOR_KEY: &'static str = "Separator";
+    /// Key combining the `Date` and `Time` fields, as in `2018/02/03 12:34:56.789`
+    const DATE_TIME_KEY: &'static str = "Date_Time";
+    /// Start of a line that lenient reading skips within header blocks
+    const COMMENT_PREFIX: char = '#';
+
+    fn with_state(
+        i_reader: R,
+        i_options: &ReadOptions,
+        i_separator: lvm::Separator,
+        i_decimal_separator: lvm::DecimalSeparator,
+        i_line_index: usize,
+    ) -> Self {
+        Deserializer {
+            column_major: i_options.column_major,
+            comments: vec![],
+            decimal_separator: i_decimal_separator.into(),
+            input: i_reader,
+            line_current: String::new(),
+            line_current_pos: 0,
+            line_index: i_line_index,
+            line_offset: 0,
+            diagnostics: None,
+            lenient: i_options.lenient,
+            line_pending: None,
+            magic_line: None,
+            max_line_length: i_options.max_line_length,
+            offset: 0,
+            #[cfg(feature = "io")]
+            packet_offsets: None,
+            raw: if i_options.raw_header {
+                Some(String::new())
+            } else {
+                None
+            },
+            raw_data: i_options.raw_data,
+            separator: i_separator.into(),
+            separator_declared: None,
+            separator_forced: false,
+            separator_key: false,
+            sequence_style: SequenceStyle::Following,
+            struct_fields: &[],
+            text_key: false,
+            transform_key: None,
+            transforms: i_options.transforms.clone(),
+        }
+    }
+
+    /// Creates a deserializer for a whole LVM file read from any source of lines
+    ///
+    /// This reads the magic line; the file can then be read with `deserialize_file`. Text held
+    /// in a `&str` can be passed directly, as can a `BufRead` through `from_buf_reader`.
+    pub fn from_reader(i_reader: R, i_options: &ReadOptions) -> Result<Self> {
+        Self::open(i_reader, i_options, None)
+    }
 
-    fn from_reader(i_reader: R) -> Result<Self> {
-        let mut lines = i_reader.lines();
+    /// Creates a deserializer for a whole LVM file, starting with its magic line
+    ///
+    /// The separator is taken from the end of the magic line, unless `i_separator` forces one
+    /// for the whole file, in which case any separators or punctuation following
+    /// `LabVIEW Measurement` are ignored.
+    fn open(
+        i_reader: R,
+        i_options: &ReadOptions,
+        i_separator: Option<lvm::Separator>,
+    ) -> Result<Self> {
+        // Create deserializer
+        let mut d = Self::with_state(
+            i_reader,
+            i_options,
+            lvm::Separator::default(),
+            lvm::DecimalSeparator::default(),
+            1,
+        );
 
         // Parse first line
-        let mut s = lines
-            .next()
+        let mut s = d.read_line()
             .ok_or_else(|| Error::from(ErrorKind::ParseEofUnexpected))
             .chain_err(|| ErrorKind::ParseLine(1))??;
-        // Pop separator
-        let separator = lvm::Separator::try_from(s.pop()
-            .ok_or_else(|| Error::from(ErrorKind::ParseEolUnexpected))
-            .chain_err(|| ErrorKind::ParseLine(1))?)?
-            .into();
+        d.magic_line = Some(s.clone());
+        match i_separator {
+            Some(separator) => {
+                d.separator = separator.into();
+                d.separator_forced = true;
+                let ending = s.get(Self::HEADER.len()..).filter(|_| s.starts_with(Self::HEADER));
+                if ending.is_some_and(|ending| !ending.contains(char::is_alphanumeric)) {
+                    s.truncate(Self::HEADER.len());
+                }
+            }
+            // Pop separator
+            None => {
+                d.separator = lvm::Separator::try_from(s.pop()
+                    .ok_or_else(|| Error::from(ErrorKind::ParseEolUnexpected))
+                    .chain_err(|| ErrorKind::ParseLine(1))?)?
+                    .into();
+            }
+        }
         // Check header
         if s != Self::HEADER {
             return Err(Error::from(ErrorKind::ParseTokenUnexpected(
@@ -45,15 +275,6 @@ impl<R: std::io::BufRead> Deserializer<R> {
             ))).chain_err(|| ErrorKind::ParseLine(1));
         }
 
-        // Create deserializer
-        let mut d = Deserializer {
-            input: lines,
-            line_current: String::new(),
-            line_current_pos: 0,
-            line_index: 1,
-            separator,
-            sequence_style: SequenceStyle::Following,
-        };
         // Load the next line
         d.parse_newline()?;
         Ok(d)
@@ -65,11 +286,27 @@ impl<R: std::io::BufRead> Deserializer<R> {
     }
 
     fn line_result<T>(&self, r: Result<T>) -> Result<T> {
-        r.chain_err(|| ErrorKind::ParseLine(self.line_index))
+        r.chain_err(|| ErrorKind::ParseColumn(self.column_index()))
+            .chain_err(|| ErrorKind::ParseLine(self.line_index))
     }
 
     fn line_error<T>(&self, e: ErrorKind) -> Result<T> {
-        Err(Error::from(e)).chain_err(|| ErrorKind::ParseLine(self.line_index))
+        self.line_result(Err(Error::from(e)))
+    }
+
+    fn column_index(&self) -> usize {
+        self.line_current
+            .get(..self.line_current_pos)
+            .unwrap_or(&self.line_current)
+            .chars()
+            .count() + 1
+    }
+
+    /// Switches to the separator declared by the header block that just ended
+    fn apply_declared_separator(&mut self) {
+        if let Some(separator) = self.separator_declared.take() {
+            self.separator = separator;
+        }
     }
 
     fn line_is_empty(&self) -> bool {
@@ -80,13 +317,62 @@ impl<R: std::io::BufRead> Deserializer<R> {
         self.line_is_empty()
     }
 
+    /// Whether the rest of the line holds no value, but at most trailing separators
+    fn peek_empty_value(&self) -> bool {
+        self.line_current[self.line_current_pos..]
+            .chars()
+            .all(|c| c == self.separator)
+    }
+
+    /// Whether the rest of the line starts with a number, so holds data rather than a key
+    ///
+    /// Without x-value columns, data rows start with a separator ahead of their first number.
+    fn peek_data_line(&self, i_x_columns: lvm::XColumns) -> bool {
+        let mut rest = &self.line_current[self.line_current_pos..];
+        if i_x_columns == lvm::XColumns::No {
+            if !rest.starts_with(self.separator) {
+                return false;
+            }
+            rest = &rest[self.separator.len_utf8()..];
+        }
+        let token = rest.split(self.separator).next().unwrap_or(rest);
+        normalize_real(token, self.decimal_separator).is_ok_and(|t| t.parse::<f64>().is_ok())
+    }
+
+    /// Whether the current line holds nothing but a comment in a field after the first
+    fn peek_comment_line(&self) -> bool {
+        self.line_current.starts_with(self.separator)
+            && !self.line_current.trim_matches(self.separator).is_empty()
+    }
+
+    /// Whether the current line is blank or a comment, so carries no header field
+    fn peek_ignored_line(&self) -> bool {
+        let line = self.line_current.trim();
+        line.is_empty() || line.starts_with(Self::COMMENT_PREFIX)
+    }
+
+    /// Whether a key ends the header block, which lenient reading also accepts padded with
+    /// whitespace or in another casing
+    fn is_end_of_header(&self, i_key: &str) -> bool {
+        i_key == Self::END_OF_HEADER
+            || self.lenient && i_key.trim().eq_ignore_ascii_case(Self::END_OF_HEADER)
+    }
+
+    /// Whether a separator just consumed ended the line and may be ignored
+    fn peek_trailing_separator(&mut self) -> bool {
+        self.lenient && self.peek_newline()
+    }
+
     fn parse_bool(&mut self) -> Result<bool> {
         let token = self.parse_token()?.to_string();
+        let matches = |options: &[&str]| options.iter().any(|o| o.eq_ignore_ascii_case(&token));
         self.line_result(
-            match token.as_ref() {
-                Self::BOOL_NO => Some(false),
-                Self::BOOL_YES => Some(true),
-                _ => None,
+            if matches(Self::BOOL_FALSE) {
+                Some(false)
+            } else if matches(Self::BOOL_TRUE) {
+                Some(true)
+            } else {
+                None
             }.ok_or_else(|| {
                 Error::from(ErrorKind::ParseTokenUnexpected(token, Self::BOOL_OPTIONS))
             }),
@@ -111,14 +397,19 @@ impl<R: std::io::BufRead> Deserializer<R> {
 
     fn parse_newline_or_eof(&mut self) -> Result<bool> {
         if self.line_is_empty() {
-            match self.input.next() {
+            if let Some(line) = self.line_pending.take() {
+                self.line_current = line;
+                self.line_current_pos = 0;
+                return Ok(true);
+            }
+            match self.read_line() {
                 Some(Ok(x)) => {
                     self.line_current = x;
                     self.line_current_pos = 0;
                     self.line_index += 1;
                     Ok(true)
                 }
-                Some(Err(e)) => self.line_result(Err(e.into())),
+                Some(Err(e)) => self.line_result(Err(e)),
                 None => Ok(false),
             }
         } else {
@@ -137,28 +428,93 @@ impl<R: std::io::BufRead> Deserializer<R> {
     }
 
     fn parse_separators(&mut self, i_count: usize) -> Result<()> {
-        let tokens = self.line_current[self.line_current_pos..]
-            .split(self.separator)
-            .take(i_count);
         let start = self.line_current_pos;
-        for token in tokens {
-            match token {
-                "" => self.line_current_pos += 1,
-                c => {
-                    return self.line_error(ErrorKind::ParseSeparatorExpected(
-                        c.to_string(),
-                        lvm::Separator::try_from(self.separator).unwrap(),
-                    ))
-                }
+        for _ in 0..i_count {
+            let rest = &self.line_current[self.line_current_pos..];
+            if rest.starts_with(self.separator) {
+                self.line_current_pos += self.separator.len_utf8();
+            } else if rest.is_empty() {
+                self.line_current_pos = start;
+                return self.line_error(ErrorKind::ParseEolUnexpected);
+            } else {
+                let token = rest.split(self.separator).next().unwrap_or(rest).to_string();
+                let separator = lvm::Separator::try_from(self.separator)?;
+                return self.line_error(ErrorKind::ParseSeparatorExpected(token, separator));
             }
         }
+        Ok(())
+    }
 
-        if self.line_current_pos - start != i_count {
-            self.line_current_pos = start;
-            self.line_error(ErrorKind::ParseEolUnexpected)
-        } else {
-            Ok(())
+    /// Replaces a `Separator` value written in any other accepted form, such as `tab` or the
+    /// escape sequence `\0x2C`, by the name of the separator
+    fn normalize_separator(&mut self) {
+        let start = self.line_current_pos;
+        let value = self.line_current[start..].split(self.separator).next().unwrap_or("");
+        if let Ok(separator) = lvm::Separator::try_from(value) {
+            let rest = self.line_current[start + value.len()..].to_string();
+            self.line_current.truncate(start);
+            self.line_current.push_str(separator.as_ref());
+            self.line_current.push_str(&rest);
+        }
+    }
+
+    /// Moves a key written after its values, as some exporters do, back in front of them
+    ///
+    /// A line is taken to be reversed when its first field is not a known key but its last
+    /// one is.
+    fn unreverse_key(&mut self) {
+        let start = self.line_current_pos;
+        let line = &self.line_current[start..];
+        let is_key = |key: &str| {
+            self.struct_fields.contains(&key)
+                || self.is_end_of_header(key)
+                || key == Self::DATE_TIME_KEY
+        };
+        if is_key(line.split(self.separator).next().unwrap_or(line)) {
+            return;
         }
+        let fields = line.trim_end_matches(self.separator);
+        let (values, key) = match fields.rfind(self.separator) {
+            Some(index) => (&fields[..index], &fields[index + self.separator.len_utf8()..]),
+            None => return,
+        };
+        if !is_key(key) {
+            return;
+        }
+        let trailing = &line[fields.len()..];
+        let line = format!("{}{}{}{}", key, self.separator, values, trailing);
+        self.line_current.truncate(start);
+        self.line_current.push_str(&line);
+    }
+
+    /// Splits the values of the `Date_Time` key just parsed into `Date` and `Time` lines
+    ///
+    /// The current line becomes the `Date` line, while the `Time` line is read next
+    /// under the same line number.
+    fn split_date_time(&mut self, i_key_start: usize) {
+        let separator = self.separator.to_string();
+        let (dates, times): (Vec<_>, Vec<_>) = self.line_current[self.line_current_pos..]
+            .split(self.separator)
+            .map(|value| {
+                let mut parts = value.trim().splitn(2, [' ', 'T']);
+                (parts.next().unwrap_or(""), parts.next().unwrap_or("").trim())
+            })
+            .unzip();
+        let prefix = &self.line_current[..i_key_start];
+        let date_line = format!("{}Date{}", prefix, dates.join(&separator));
+        self.line_pending = Some(format!("{}Time{}", prefix, times.join(&separator)));
+        self.line_current_pos = i_key_start + "Date".len();
+        self.line_current = date_line;
+    }
+
+    /// Parses a floating-point number written with the file's decimal separator
+    ///
+    /// When the decimal separator is a comma, dots are taken as digit grouping and dropped.
+    fn parse_real<T: std::str::FromStr<Err = std::num::ParseFloatError>>(&mut self) -> Result<T> {
+        let decimal_separator = self.decimal_separator;
+        let token = self.parse_token()?;
+        T::from_str(&normalize_real(token, decimal_separator)?)
+            .map_err(|e| ErrorKind::ParseFloatError(e).into())
     }
 
     fn parse_sequence(&mut self) -> Sequence<R> {
@@ -178,23 +534,109 @@ impl<R: std::io::BufRead> Deserializer<R> {
         }
     }
 
+    /// Parses the remainder of the line, separators included
+    fn parse_text(&mut self) -> &str {
+        let start = self.line_current_pos;
+        self.line_current_pos = self.line_current.len();
+        &self.line_current[start..]
+    }
+
+    /// Parses a quoted token, unescaping doubled quotes
+    fn parse_quoted(&mut self) -> Result<String> {
+        let start = self.line_current_pos + QUOTE.len_utf8();
+        let mut value = String::new();
+        let mut chars = self.line_current[start..].char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            if c != QUOTE {
+                value.push(c);
+            } else if chars.peek().map(|&(_, next)| next) == Some(QUOTE) {
+                chars.next();
+                value.push(QUOTE);
+            } else {
+                self.line_current_pos = start + index + QUOTE.len_utf8();
+                return Ok(value);
+            }
+        }
+        self.line_error(ErrorKind::ParseEolUnexpected)
+    }
+
+    /// Parses a string value
+    ///
+    /// Values of free-text keys extend to the end of the line,
+    /// while quoted values may contain the separator.
+    fn parse_string(&mut self) -> Result<Cow<'_, str>> {
+        if self.text_key {
+            Ok(Cow::Borrowed(self.parse_text()))
+        } else if self.line_current[self.line_current_pos..].starts_with(QUOTE) {
+            self.parse_quoted().map(Cow::Owned)
+        } else {
+            self.parse_token().map(Cow::Borrowed)
+        }
+    }
+
     fn parse_tuple(&mut self, i_length: usize) -> Tuple<R> {
         Tuple::new(i_length, self)
     }
 
-    fn set_sequence_style(&mut self, i_style: SequenceStyle) {
-        self.sequence_style = i_style;
+    /// Reads the next line without its terminator, recording the raw text if requested
+    fn read_line(&mut self) -> Option<Result<String>> {
+        let mut line = String::new();
+        let read = match self.max_line_length {
+            // Room for the longest line terminator, so a line at the limit still ends
+            Some(max) => self.input.read_line_max(&mut line, max.saturating_add(2)),
+            None => self.input.read_line(&mut line),
+        };
+        match read {
+            Ok(0) => None,
+            Ok(read) => {
+                self.line_offset = self.offset;
+                self.offset += read as u64;
+                if let Some(ref mut raw) = self.raw {
+                    raw.push_str(&line);
+                }
+                if line.ends_with('\n') {
+                    line.pop();
+                    if line.ends_with('\r') {
+                        line.pop();
+                    }
+                }
+                match self.max_line_length {
+                    Some(max) if line.len() > max => {
+                        Some(Err(ErrorKind::LineTooLong(max).into()))
+                    }
+                    _ => Some(Ok(line)),
+                }
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    /// Runs `f` with the specified sequence style, restoring the previous style afterwards
+    fn with_sequence_style<T, F: FnOnce(&mut Self) -> T>(
+        &mut self,
+        i_style: SequenceStyle,
+        f: F,
+    ) -> T {
+        let previous = std::mem::replace(&mut self.sequence_style, i_style);
+        let r = f(self);
+        self.sequence_style = previous;
+        r
+    }
+
+    /// Whether each value is followed by a separator
+    fn following(&self) -> bool {
+        matches!(self.sequence_style, SequenceStyle::Following)
     }
 }
 
 #[must_use]
-struct Tuple<'a, R: std::io::BufRead + 'a> {
+struct Tuple<'a, R: ReadLine + 'a> {
     de: &'a mut Deserializer<R>,
     length: usize,
     index: usize,
 }
 
-impl<'a, R: std::io::BufRead> Tuple<'a, R> {
+impl<'a, R: ReadLine> Tuple<'a, R> {
     fn new(i_count: usize, i_de: &'a mut Deserializer<R>) -> Self {
         Tuple {
             de: i_de,
@@ -204,7 +646,7 @@ impl<'a, R: std::io::BufRead> Tuple<'a, R> {
     }
 }
 
-impl<'a, 'de: 'a, R: std::io::BufRead + 'a> serde::de::SeqAccess<'de> for Tuple<'a, R> {
+impl<'a, 'de: 'a, R: ReadLine + 'a> serde::de::SeqAccess<'de> for Tuple<'a, R> {
     type Error = Error;
 
     fn next_element_seed<T: serde::de::DeserializeSeed<'de>>(
@@ -214,6 +656,10 @@ impl<'a, 'de: 'a, R: std::io::BufRead + 'a> serde::de::SeqAccess<'de> for Tuple<
         if self.index >= self.length {
             Ok(None)
         } else {
+            // Like sequence elements, a tuple element is followed by a separator
+            if self.index > 0 && self.de.following() && !self.de.peek_newline() {
+                self.de.parse_separators(1)?;
+            }
             self.index += 1;
             seed.deserialize(&mut *self.de).map(Some)
         }
@@ -229,13 +675,13 @@ enum SequenceStyle {
 }
 
 #[must_use]
-struct Sequence<'a, R: std::io::BufRead + 'a> {
+struct Sequence<'a, R: ReadLine + 'a> {
     de: &'a mut Deserializer<R>,
     first: bool,
     style: SequenceStyle,
 }
 
-impl<'a, R: std::io::BufRead> Sequence<'a, R> {
+impl<'a, R: ReadLine> Sequence<'a, R> {
     fn new(i_style: SequenceStyle, i_de: &'a mut Deserializer<R>) -> Self {
         Sequence {
             de: i_de,
@@ -245,7 +691,7 @@ impl<'a, R: std::io::BufRead> Sequence<'a, R> {
     }
 }
 
-impl<'a, 'de: 'a, R: std::io::BufRead + 'a> serde::de::SeqAccess<'de> for Sequence<'a, R> {
+impl<'a, 'de: 'a, R: ReadLine + 'a> serde::de::SeqAccess<'de> for Sequence<'a, R> {
     type Error = Error;
 
     fn next_element_seed<T: serde::de::DeserializeSeed<'de>>(
@@ -266,7 +712,10 @@ impl<'a, 'de: 'a, R: std::io::BufRead + 'a> serde::de::SeqAccess<'de> for Sequen
                     return Ok(None);
                 };
                 if !self.first {
-                    self.de.parse_separators(1)?
+                    self.de.parse_separators(1)?;
+                    if self.de.peek_trailing_separator() {
+                        return Ok(None);
+                    }
                 };
             }
             SequenceStyle::Preceding => {
@@ -274,6 +723,9 @@ impl<'a, 'de: 'a, R: std::io::BufRead + 'a> serde::de::SeqAccess<'de> for Sequen
                     return Ok(None);
                 };
                 self.de.parse_separators(1)?;
+                if self.de.peek_trailing_separator() {
+                    return Ok(None);
+                }
             }
         }
         self.first = false;
@@ -281,32 +733,81 @@ impl<'a, 'de: 'a, R: std::io::BufRead + 'a> serde::de::SeqAccess<'de> for Sequen
     }
 }
 
-impl<'de, R: std::io::BufRead> serde::de::MapAccess<'de> for Deserializer<R> {
+impl<'de, R: ReadLine> serde::de::MapAccess<'de> for Deserializer<R> {
     type Error = Error;
 
     fn next_key_seed<K: serde::de::DeserializeSeed<'de>>(
         &mut self,
         seed: K,
     ) -> Result<Option<K::Value>> {
-        match self.parse_token()? {
-            "***End_of_Header***" => Ok(None),
-            t => seed.deserialize(t.into_deserializer()).map(Some),
+        self.separator_key = false;
+        self.text_key = false;
+        while self.lenient && self.peek_ignored_line() {
+            self.line_current_pos = self.line_current.len();
+            self.parse_newline()?;
+        }
+        if self.lenient {
+            self.unreverse_key();
+        }
+        let start = self.line_current_pos;
+        self.parse_token()?;
+        if &self.line_current[start..self.line_current_pos] == Self::DATE_TIME_KEY {
+            self.split_date_time(start);
+        }
+        match &self.line_current[start..self.line_current_pos] {
+            t if self.is_end_of_header(t) => Ok(None),
+            Self::SEPARATOR_KEY => {
+                self.separator_key = true;
+                seed.deserialize(Self::SEPARATOR_KEY.into_deserializer())
+                    .map(Some)
+            }
+            t => {
+                self.text_key = TEXT_KEYS.contains(&t);
+                self.transform_key = if self.transforms.contains(t) {
+                    Some(t.to_string())
+                } else {
+                    None
+                };
+                seed.deserialize(t.into_deserializer()).map(Some)
+            }
         }
     }
 
     fn next_value_seed<V: serde::de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
         self.parse_separators(1)?;
+        let start = self.line_current_pos;
+        if let Some(key) = self.transform_key.take() {
+            let value = self.transforms.apply(&key, &self.line_current[start..]);
+            self.line_current.truncate(start);
+            self.line_current.push_str(&value);
+        }
+        if self.separator_key {
+            self.normalize_separator();
+        }
         let r = seed.deserialize(&mut *self)?;
+        if self.separator_key {
+            // The value may be padded with trailing separators
+            let value = self.line_current[start..self.line_current_pos]
+                .trim_end_matches(self.separator);
+            match lvm::Separator::from_name(value) {
+                Some(separator) if !self.separator_forced => {
+                    self.separator_declared = Some(separator.into());
+                }
+                _ => {}
+            }
+        }
+        self.text_key = false;
         self.parse_newline()?;
         Ok(r)
     }
 }
 
-impl<'de, 'a, R: std::io::BufRead> serde::de::Deserializer<'de> for &'a mut Deserializer<R> {
+impl<'de, 'a, R: ReadLine> serde::de::Deserializer<'de> for &'a mut Deserializer<R> {
     type Error = Error;
 
     fn deserialize_any<V: serde::de::Visitor<'de>>(self, _: V) -> Result<V::Value> {
-        unimplemented!()
+        // LVM text does not describe its own types
+        Err(ErrorKind::Unsupported("Reading without a type hint").into())
     }
 
     fn deserialize_bool<V: serde::de::Visitor<'de>>(self, v: V) -> Result<V::Value> {
@@ -314,41 +815,44 @@ impl<'de, 'a, R: std::io::BufRead> serde::de::Deserializer<'de> for &'a mut Dese
     }
 
     fn deserialize_byte_buf<V: serde::de::Visitor<'de>>(self, _: V) -> Result<V::Value> {
-        unimplemented!()
+        Err(ErrorKind::Unsupported("Reading bytes").into())
     }
 
     fn deserialize_bytes<V: serde::de::Visitor<'de>>(self, _: V) -> Result<V::Value> {
-        unimplemented!()
+        Err(ErrorKind::Unsupported("Reading bytes").into())
     }
 
     fn deserialize_char<V: serde::de::Visitor<'de>>(self, _v: V) -> Result<V::Value> {
-        unimplemented!()
-        //v.visit_char(self.parse_char()?)
+        Err(ErrorKind::Unsupported("Reading a char").into())
     }
 
     fn deserialize_enum<V: serde::de::Visitor<'de>>(
         self,
         _name: &'static str,
-        _variants: &'static [&'static str],
+        variants: &'static [&'static str],
         v: V,
     ) -> Result<V::Value> {
-        v.visit_enum(self.parse_token()?.into_deserializer())
+        let token = self.parse_token()?;
+        // LabVIEW can export enums as the index of their value
+        let variant = match token.parse::<usize>() {
+            Ok(index) => variants.get(index).cloned(),
+            Err(_) => variants.iter().find(|&&variant| variant == token).cloned(),
+        };
+        match variant {
+            Some(variant) => v.visit_enum(variant.into_deserializer()),
+            None => {
+                let token = token.to_string();
+                self.line_error(ErrorKind::ParseTokenUnexpected(token, variants))
+            }
+        }
     }
 
     fn deserialize_f32<V: serde::de::Visitor<'de>>(self, v: V) -> Result<V::Value> {
-        // v.visit_f32(self.parse_real::<f32>()?)
-        use std::str::FromStr;
-        self.parse_token()
-            .and_then(|s| f32::from_str(s).map_err(|e| ErrorKind::ParseFloatError(e).into()))
-            .and_then(|f| v.visit_f32(f))
+        v.visit_f32(self.parse_real::<f32>()?)
     }
 
     fn deserialize_f64<V: serde::de::Visitor<'de>>(self, v: V) -> Result<V::Value> {
-        // v.visit_f64(self.parse_real::<f64>()?)
-        use std::str::FromStr;
-        self.parse_token()
-            .and_then(|s| f64::from_str(s).map_err(|e| ErrorKind::ParseFloatError(e).into()))
-            .and_then(|f| v.visit_f64(f))
+        v.visit_f64(self.parse_real::<f64>()?)
     }
 
     fn deserialize_i8<V: serde::de::Visitor<'de>>(self, v: V) -> Result<V::Value> {
@@ -367,8 +871,13 @@ impl<'de, 'a, R: std::io::BufRead> serde::de::Deserializer<'de> for &'a mut Dese
         v.visit_i64(self.parse_integer::<i64>()?)
     }
 
-    fn deserialize_ignored_any<V: serde::de::Visitor<'de>>(self, _: V) -> Result<V::Value> {
-        unimplemented!()
+    fn deserialize_i128<V: serde::de::Visitor<'de>>(self, v: V) -> Result<V::Value> {
+        v.visit_i128(self.parse_integer::<i128>()?)
+    }
+
+    fn deserialize_ignored_any<V: serde::de::Visitor<'de>>(self, v: V) -> Result<V::Value> {
+        self.line_current_pos = self.line_current.len();
+        v.visit_unit()
     }
 
     fn deserialize_seq<V: serde::de::Visitor<'de>>(self, v: V) -> Result<V::Value> {
@@ -391,8 +900,14 @@ impl<'de, 'a, R: std::io::BufRead> serde::de::Deserializer<'de> for &'a mut Dese
         v.visit_u64(self.parse_integer::<u64>()?)
     }
 
+    fn deserialize_u128<V: serde::de::Visitor<'de>>(self, v: V) -> Result<V::Value> {
+        v.visit_u128(self.parse_integer::<u128>()?)
+    }
+
     fn deserialize_option<V: serde::de::Visitor<'de>>(self, v: V) -> Result<V::Value> {
-        if self.peek_newline() {
+        // An empty value, as written for `None`, may still be padded with separators
+        if self.peek_empty_value() {
+            self.line_current_pos = self.line_current.len();
             v.visit_none()
         } else {
             v.visit_some(self)
@@ -400,15 +915,16 @@ impl<'de, 'a, R: std::io::BufRead> serde::de::Deserializer<'de> for &'a mut Dese
     }
 
     fn deserialize_map<V: serde::de::Visitor<'de>>(self, _v: V) -> Result<V::Value> {
-        unimplemented!()
+        Err(ErrorKind::Unsupported("Reading a map").into())
     }
 
     fn deserialize_struct<V: serde::de::Visitor<'de>>(
         mut self,
         _name: &'static str,
-        _fields: &'static [&'static str],
+        fields: &'static [&'static str],
         v: V,
     ) -> Result<V::Value> {
+        self.struct_fields = fields;
         let r = v.visit_map(&mut self)?;
         self.parse_separators(1)?;
         Ok(r)
@@ -427,11 +943,14 @@ impl<'de, 'a, R: std::io::BufRead> serde::de::Deserializer<'de> for &'a mut Dese
     }
 
     fn deserialize_str<V: serde::de::Visitor<'de>>(self, v: V) -> Result<V::Value> {
-        v.visit_str(self.parse_token()?)
+        match self.parse_string()? {
+            Cow::Borrowed(s) => v.visit_str(s),
+            Cow::Owned(s) => v.visit_string(s),
+        }
     }
 
     fn deserialize_string<V: serde::de::Visitor<'de>>(self, v: V) -> Result<V::Value> {
-        v.visit_string(self.parse_token()?.to_string())
+        v.visit_string(self.parse_string()?.into_owned())
     }
 
     fn deserialize_tuple<V: serde::de::Visitor<'de>>(self, len: usize, v: V) -> Result<V::Value> {
@@ -444,11 +963,11 @@ impl<'de, 'a, R: std::io::BufRead> serde::de::Deserializer<'de> for &'a mut Dese
         _len: usize,
         _v: V,
     ) -> Result<V::Value> {
-        unimplemented!()
+        Err(ErrorKind::Unsupported("Reading a tuple struct").into())
     }
 
     fn deserialize_unit<V: serde::de::Visitor<'de>>(self, _v: V) -> Result<V::Value> {
-        unimplemented!()
+        Err(ErrorKind::Unsupported("Reading a unit").into())
     }
 
     fn deserialize_unit_struct<V: serde::de::Visitor<'de>>(
@@ -456,65 +975,3533 @@ impl<'de, 'a, R: std::io::BufRead> serde::de::Deserializer<'de> for &'a mut Dese
         _name: &'static str,
         _v: V,
     ) -> Result<V::Value> {
-        unimplemented!()
+        Err(ErrorKind::Unsupported("Reading a unit struct").into())
     }
 }
 
-/// Deserializes LVM file data from the specified reader
-pub fn from_reader<R: std::io::Read>(i_reader: R) -> Result<lvm::File> {
-    let buf_reader = std::io::BufReader::new(i_reader);
-    let mut deserializer = Deserializer::from_reader(buf_reader)?;
+#[derive(Debug)]
+#[must_use]
+struct Serializer<W: WriteText> {
+    decimal_separator: char,
+    header_ended: bool,
+    line_open: bool,
+    output: W,
+    separator: char,
+    sequence_style: SequenceStyle,
+    skip_defaults: bool,
+    text_key: bool,
+}
 
-    let file_header: lvm::FileHeader = deserializer.deserialize()?;
+impl<W: WriteText> Serializer<W> {
+    const BOOL_YES: &'static str = "Yes";
+    const BOOL_NO: &'static str = "No";
+    const END_OF_HEADER: &'static str = "***End_of_Header***";
+    const HEADER: &'static str = "LabVIEW Measurement";
+    const SEPARATOR_KEY: &'static str = "Separator";
 
-    let file_measurements = {
-        deserializer.parse_newline()?;
-        deserializer.parse_separators(1)?;
+    fn new(i_writer: W, i_separator: lvm::Separator) -> Self {
+        Serializer {
+            decimal_separator: lvm::DecimalSeparator::default().into(),
+            header_ended: false,
+            line_open: false,
+            output: i_writer,
+            separator: i_separator.into(),
+            sequence_style: SequenceStyle::Following,
+            skip_defaults: false,
+            text_key: false,
+        }
+    }
 
-        let mut measurements = vec![];
-        loop {
-            if !deserializer.parse_newline_or_eof()? {
-                break;
-            }
-            deserializer.set_sequence_style(SequenceStyle::Following);
-            let measurement_header: lvm::MeasurementHeader = deserializer.deserialize()?;
-            deserializer.parse_separators(measurement_header.channels.0)?;
-            deserializer.parse_newline()?;
-
-            deserializer.set_sequence_style(SequenceStyle::FollowingSkipLast);
-            let data_headings: Vec<String> = deserializer.deserialize()?;
-            deserializer.parse_newline()?;
-
-            deserializer.set_sequence_style(match file_header.x_columns {
-                lvm::XColumns::No => SequenceStyle::Preceding,
-                lvm::XColumns::One => SequenceStyle::FollowingSkipLast,
-                _ => unimplemented!(),
-            });
-            let mut data_rows = vec![];
-            loop {
-                if deserializer.peek_newline() {
-                    break;
-                }
-                let data_row: lvm::DataRow = deserializer.deserialize()?;
-                data_rows.push(data_row);
-                if !deserializer.parse_newline_or_eof()? {
-                    break;
+    fn serialize<T: serde::ser::Serialize>(&mut self, i_value: &T) -> Result<()> {
+        i_value.serialize(&mut *self)
+    }
+
+    fn set_sequence_style(&mut self, i_style: SequenceStyle) {
+        self.sequence_style = i_style;
+    }
+
+    fn write_header(&mut self, i_magic_line: Option<&str>) -> Result<()> {
+        match i_magic_line {
+            Some(magic_line) => self.write_token(magic_line)?,
+            None => {
+                self.write_token(Self::HEADER)?;
+                self.write_separators(1)?;
+            }
+        }
+        self.write_newline()
+    }
+
+    /// Writes a header field on a line of its own
+    fn write_field<T: serde::ser::Serialize + ?Sized>(
+        &mut self,
+        i_key: &'static str,
+        i_value: &T,
+    ) -> Result<()> {
+        if self.skip_defaults {
+            let mut field = Serializer::new(String::new(), lvm::Separator::default());
+            field.decimal_separator = self.decimal_separator;
+            field.separator = self.separator;
+            field.sequence_style = self.sequence_style;
+            field.text_key = TEXT_KEYS.contains(&i_key);
+            i_value.serialize(&mut field)?;
+            if field.output.is_empty() || DEFAULT_FIELDS.contains(&(i_key, &field.output)) {
+                return Ok(());
+            }
+            self.write_token(i_key)?;
+            self.write_separators(1)?;
+            self.write_token(&field.output)?;
+            return self.write_newline();
+        }
+        self.write_token(i_key)?;
+        self.write_separators(1)?;
+        self.text_key = TEXT_KEYS.contains(&i_key);
+        let r = i_value.serialize(&mut *self);
+        self.text_key = false;
+        r?;
+        self.write_newline()
+    }
+
+    fn write_newline(&mut self) -> Result<()> {
+        self.write_token("\n")?;
+        self.line_open = false;
+        Ok(())
+    }
+
+    /// Ends the current line, unless nothing was written to it yet
+    fn end_line(&mut self) -> Result<()> {
+        if self.line_open {
+            self.write_newline()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Ends the file header block, writing its terminator unless it was written already
+    fn end_header(&mut self) -> Result<()> {
+        if !self.header_ended {
+            self.end_line()?;
+            self.write_token(Self::END_OF_HEADER)?;
+            self.write_separators(1)?;
+            self.header_ended = true;
+        }
+        self.end_line()
+    }
+
+    /// Completes the output, which must be done once everything is serialized
+    ///
+    /// A file without a header terminator or a final newline may not read back.
+    fn finish(&mut self) -> Result<()> {
+        self.end_header()
+    }
+
+    /// Writes a string value, quoting it if it would otherwise be misread
+    fn write_string(&mut self, i_value: &str) -> Result<()> {
+        if self.text_key || !(i_value.contains(self.separator) || i_value.starts_with(QUOTE)) {
+            return self.write_token(i_value);
+        }
+        let mut escaped = String::from(QUOTE);
+        escaped.push_str(&i_value.replace(QUOTE, "\"\""));
+        escaped.push(QUOTE);
+        self.write_token(&escaped)
+    }
+
+    /// Writes a floating-point number with the file's decimal separator
+    fn write_real<T: std::fmt::Display>(&mut self, i_value: T) -> Result<()> {
+        if self.decimal_separator == '.' {
+            self.write_token(&i_value)
+        } else {
+            let text = i_value.to_string().replace('.', &self.decimal_separator.to_string());
+            self.write_token(&text)
+        }
+    }
+
+    fn write_separators(&mut self, i_count: usize) -> Result<()> {
+        for _ in 0..i_count {
+            write!(self.output, "{}", self.separator)?;
+            self.line_open = true;
+        }
+        Ok(())
+    }
+
+    fn write_token<T: std::fmt::Display + ?Sized>(&mut self, i_token: &T) -> Result<()> {
+        write!(self.output, "{}", i_token)?;
+        self.line_open = true;
+        Ok(())
+    }
+}
+
+fn unsupported<T>(i_what: &str) -> Result<T> {
+    Err(ErrorKind::Serialize(format!("{} cannot be written to an LVM file", i_what)).into())
+}
+
+#[must_use]
+struct SequenceSerializer<'a, W: WriteText + 'a> {
+    ser: &'a mut Serializer<W>,
+    first: bool,
+    style: SequenceStyle,
+}
+
+impl<'a, W: WriteText + 'a> serde::ser::SerializeSeq for SequenceSerializer<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: serde::ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        match self.style {
+            SequenceStyle::Following => {}
+            SequenceStyle::FollowingSkipLast => {
+                if !self.first {
+                    self.ser.write_separators(1)?;
                 }
             }
+            SequenceStyle::Preceding => self.ser.write_separators(1)?,
+        }
+        self.first = false;
+        value.serialize(&mut *self.ser)?;
+        if let SequenceStyle::Following = self.style {
+            self.ser.write_separators(1)?;
+        }
+        Ok(())
+    }
 
-            measurements.push(lvm::Measurement {
-                header: measurement_header,
-                data_headings,
-                data: data_rows,
-            });
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[must_use]
+struct TupleSerializer<'a, W: WriteText + 'a> {
+    ser: &'a mut Serializer<W>,
+    first: bool,
+}
+
+impl<'a, W: WriteText + 'a> serde::ser::SerializeTuple for TupleSerializer<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: serde::ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        if let SequenceStyle::Following = self.ser.sequence_style {
+            if !self.first {
+                self.ser.write_separators(1)?;
+            }
         }
-        measurements
-    };
+        self.first = false;
+        value.serialize(&mut *self.ser)
+    }
 
-    let lvm_file = lvm::File {
-        header: file_header,
-        measurements: file_measurements,
-    };
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
 
-    Ok(lvm_file)
+impl<'a, W: WriteText + 'a> serde::ser::SerializeStruct for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: serde::ser::Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        if key == Serializer::<W>::SEPARATOR_KEY {
+            // The field names the separator written with, which need not be the one read
+            let separator = lvm::Separator::try_from(self.separator)?;
+            return self.write_field(key, &separator);
+        }
+        self.write_field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.write_token(Serializer::<W>::END_OF_HEADER)?;
+        self.write_separators(1)?;
+        self.header_ended = true;
+        Ok(())
+    }
+}
+
+impl<'a, W: WriteText> serde::ser::Serializer for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = SequenceSerializer<'a, W>;
+    type SerializeTuple = TupleSerializer<'a, W>;
+    type SerializeTupleStruct = serde::ser::Impossible<(), Error>;
+    type SerializeTupleVariant = serde::ser::Impossible<(), Error>;
+    type SerializeMap = serde::ser::Impossible<(), Error>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = serde::ser::Impossible<(), Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.write_token(if v {
+            Serializer::<W>::BOOL_YES
+        } else {
+            Serializer::<W>::BOOL_NO
+        })
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.write_token(&v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.write_token(&v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.write_token(&v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.write_token(&v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.write_token(&v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.write_token(&v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.write_token(&v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.write_token(&v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.write_token(&v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.write_token(&v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.write_real(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.write_real(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.write_token(&v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.write_string(v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<()> {
+        unsupported("Bytes")
+    }
+
+    /// Writes nothing, leaving the key with an empty value that reads back as `None`
+    fn serialize_none(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_some<T: serde::ser::Serialize + ?Sized>(self, v: &T) -> Result<()> {
+        v.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.write_token(variant)
+    }
+
+    fn serialize_newtype_struct<T: serde::ser::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        v: &T,
+    ) -> Result<()> {
+        v.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: serde::ser::Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _v: &T,
+    ) -> Result<()> {
+        unsupported(name)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        let style = self.sequence_style;
+        Ok(SequenceSerializer {
+            ser: self,
+            first: true,
+            style,
+        })
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Ok(TupleSerializer {
+            ser: self,
+            first: true,
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        unsupported(name)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        unsupported(name)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        unsupported("Maps")
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        unsupported(name)
+    }
+}
+
+/// Function rewriting the raw text of a header value
+type Transform = std::sync::Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+/// Transforms applied to the raw text of header values, keyed by field name
+///
+/// A transform receives the rest of the line following the field name and its separator,
+/// and returns the text to parse in its place.
+#[derive(Clone, Default)]
+pub struct Transforms(std::collections::HashMap<String, Transform>);
+
+impl Transforms {
+    /// Creates an empty set of transforms
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a transform for the values of the specified field, replacing any previous one
+    pub fn insert<F: Fn(&str) -> String + Send + Sync + 'static>(
+        &mut self,
+        i_key: &str,
+        i_transform: F,
+    ) -> &mut Self {
+        self.0.insert(i_key.to_string(), std::sync::Arc::new(i_transform));
+        self
+    }
+
+    fn apply(&self, i_key: &str, i_value: &str) -> String {
+        match self.0.get(i_key) {
+            Some(transform) => transform(i_value),
+            None => i_value.to_string(),
+        }
+    }
+
+    fn contains(&self, i_key: &str) -> bool {
+        self.0.contains_key(i_key)
+    }
+}
+
+impl std::fmt::Debug for Transforms {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+/// Options controlling how LVM file data is read
+#[derive(Clone, Debug, Default)]
+pub struct ReadOptions {
+    /// Tolerate common deviations from the format, such as a trailing separator on data rows,
+    /// blank and `#` comment lines within header blocks, header values written before their
+    /// keys, or a padded or differently cased `***End_of_Header***`
+    pub lenient: bool,
+    /// Retain the text of the file header block verbatim in `File::raw_header`
+    pub raw_header: bool,
+    /// Retain the text of every data value verbatim in `Measurement::raw_data`
+    pub raw_data: bool,
+    /// Rewrite the raw text of header values before they are parsed
+    pub transforms: Transforms,
+    /// Read data stored one column after another, one value per line, rather than in rows
+    pub column_major: bool,
+    /// Fail on lines longer than this many bytes, not counting the line terminator, before
+    /// reading them whole
+    pub max_line_length: Option<usize>,
+}
+
+/// How data values are formatted when written
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Precision {
+    /// The shortest text that reads back as the same value
+    #[default]
+    Shortest,
+    /// A fixed number of digits after the decimal separator, as in `1.500`
+    Decimals(usize),
+    /// A fixed number of significant digits, as in `0.0123` for three of them
+    Significant(usize),
+}
+
+impl Precision {
+    /// Formats a value, leaving infinities and NaN to the shortest format
+    fn format(self, i_value: f64) -> Option<String> {
+        if !i_value.is_finite() {
+            return None;
+        }
+        let decimals = match self {
+            Precision::Shortest => return None,
+            Precision::Decimals(decimals) => decimals,
+            Precision::Significant(digits) => {
+                let magnitude = if i_value == 0.0 {
+                    0
+                } else {
+                    i_value.abs().log10().floor() as i64
+                };
+                let decimals = digits.max(1) as i64 - 1 - magnitude;
+                if decimals < 0 {
+                    // Round away the digits left of the decimal separator that are not kept
+                    let scale = 10f64.powi(-decimals as i32);
+                    return Some(format!("{:.0}", (i_value / scale).round() * scale));
+                }
+                decimals as usize
+            }
+        };
+        Some(format!("{:.*}", decimals, i_value))
+    }
+}
+
+/// Options controlling how LVM file data is written
+#[derive(Clone, Debug, Default)]
+pub struct WriteOptions {
+    /// Emit `File::raw_header` verbatim, when present, instead of the parsed file header
+    ///
+    /// Otherwise the parsed file header follows `FileHeader::magic_line`, when present.
+    pub raw_header: bool,
+    /// Leave out header fields that are empty or hold the value readers assume by default
+    pub skip_defaults: bool,
+    /// Format of data values in columns without an entry in `column_precision`
+    pub precision: Precision,
+    /// Format of data values by column, counting x-value columns
+    ///
+    /// Values retained verbatim in `Measurement::raw_data` are written as read regardless.
+    pub column_precision: Vec<Precision>,
+    /// Separator to write the file with, instead of `FileHeader::separator`
+    ///
+    /// Text is quoted as needed for this separator. As they hold the separator read, neither
+    /// the raw header nor the magic line is re-emitted when it differs.
+    pub separator: Option<lvm::Separator>,
+}
+
+/// Deserializes LVM file data from the specified string
+pub fn from_str(i_text: &str) -> Result<lvm::File> {
+    from_str_with_options(i_text, &ReadOptions::default())
+}
+
+/// Deserializes LVM file data from the specified string using the specified options
+pub fn from_str_with_options(i_text: &str, i_options: &ReadOptions) -> Result<lvm::File> {
+    let mut deserializer = Deserializer::from_reader(i_text, i_options)?;
+    deserialize_file(&mut deserializer, 0, |_| {})
+}
+
+/// Deserializes LVM file data from the specified reader
+#[cfg(feature = "io")]
+pub fn from_reader<R: std::io::Read>(i_reader: R) -> Result<lvm::File> {
+    from_reader_with_options(i_reader, &ReadOptions::default())
+}
+
+/// Deserializes LVM file data from the specified reader using the specified options
+#[cfg(feature = "io")]
+pub fn from_reader_with_options<R: std::io::Read>(
+    i_reader: R,
+    i_options: &ReadOptions,
+) -> Result<lvm::File> {
+    let buf_reader = IoReader(std::io::BufReader::new(i_reader));
+    let mut deserializer = Deserializer::from_reader(buf_reader, i_options)?;
+    deserialize_file(&mut deserializer, 0, |_| {})
+}
+
+/// Deserializes LVM file data from the specified reader, splitting every line on `i_separator`
+///
+/// This overrides both the separator ending the `LabVIEW Measurement` line and the
+/// `Separator` field, for files that get them wrong.
+#[cfg(feature = "io")]
+pub fn from_reader_with_separator<R: std::io::Read>(
+    i_reader: R,
+    i_separator: lvm::Separator,
+) -> Result<lvm::File> {
+    let buf_reader = IoReader(std::io::BufReader::new(i_reader));
+    let mut deserializer =
+        Deserializer::open(buf_reader, &ReadOptions::default(), Some(i_separator))?;
+    deserialize_file(&mut deserializer, 0, |_| {})
+}
+
+/// Deserializes LVM file data from the specified reader, skipping data rows that do not parse
+///
+/// The errors of the skipped rows are returned along with the 1-based lines they were found
+/// on. Headers are still read strictly, so any error in them fails the whole file.
+#[cfg(feature = "io")]
+pub fn from_reader_lossy<R: std::io::Read>(
+    i_reader: R,
+) -> Result<(lvm::File, Vec<(usize, Error)>)> {
+    let buf_reader = IoReader(std::io::BufReader::new(i_reader));
+    let mut deserializer = Deserializer::from_reader(buf_reader, &ReadOptions::default())?;
+    deserializer.diagnostics = Some(vec![]);
+    let file = deserialize_file(&mut deserializer, 0, |_| {})?;
+    Ok((file, deserializer.diagnostics.take().unwrap_or_default()))
+}
+
+/// Deserializes LVM file data from the specified reader, reporting progress along the way
+///
+/// `o_progress` is called with the current line index after every `i_interval` data rows;
+/// an interval of zero disables it.
+#[cfg(feature = "io")]
+pub fn from_reader_with_progress<R: std::io::Read, F: FnMut(usize)>(
+    i_reader: R,
+    i_options: &ReadOptions,
+    i_interval: usize,
+    o_progress: F,
+) -> Result<lvm::File> {
+    let buf_reader = IoReader(std::io::BufReader::new(i_reader));
+    let mut deserializer = Deserializer::from_reader(buf_reader, i_options)?;
+    deserialize_file(&mut deserializer, i_interval, o_progress)
+}
+
+fn deserialize_file<R: ReadLine, F: FnMut(usize)>(
+    deserializer: &mut Deserializer<R>,
+    i_progress_interval: usize,
+    o_progress: F,
+) -> Result<lvm::File> {
+    let mut file_header: lvm::FileHeader = deserializer.deserialize()?;
+    let magic_separator = lvm::Separator::try_from(deserializer.separator)?;
+    file_header.set_observed_separators(magic_separator, deserializer.separator_declared.is_some());
+    if let Some(magic_line) = deserializer.magic_line.take() {
+        file_header.set_magic_line(magic_line);
+    }
+    file_header.check_separators()?;
+    if !file_header.writer_version.is_supported() {
+        warn!(
+            "File was written with format version {}.{}, newer than the supported {}.{}",
+            file_header.writer_version.major,
+            file_header.writer_version.minor,
+            lvm::SUPPORTED_VERSION.0,
+            lvm::SUPPORTED_VERSION.1
+        );
+    }
+    let raw_header = deserializer.raw.take();
+    deserializer.apply_declared_separator();
+    deserializer.decimal_separator = file_header.decimal_separator().into();
+
+    deserializer.parse_newline()?;
+    deserializer.parse_separators(1)?;
+    let file_measurements =
+        deserializer.parse_measurements(
+            file_header.x_columns,
+            file_header.multi_headings(),
+            i_progress_interval,
+            o_progress,
+        )?;
+
+    let lvm_file = lvm::File {
+        header: file_header,
+        measurements: file_measurements,
+        raw_header,
+        comments: std::mem::take(&mut deserializer.comments),
+    };
+
+    Ok(lvm_file)
+}
+
+#[cfg(feature = "io")]
+impl<R: std::io::BufRead> Deserializer<IoReader<R>> {
+    /// Creates a deserializer for LVM text that does not start with the file's magic line
+    ///
+    /// This suits input whose file header was supplied out-of-band. The reader must be
+    /// positioned at the start of a line, which is numbered 1 in errors. Fields are split on
+    /// `i_separator` until a header block declares another, and numbers are read with
+    /// `i_decimal_separator`.
+    pub fn new(
+        i_reader: R,
+        i_separator: lvm::Separator,
+        i_decimal_separator: lvm::DecimalSeparator,
+        i_options: &ReadOptions,
+    ) -> Self {
+        Deserializer::with_state(
+            IoReader(i_reader),
+            i_options,
+            i_separator,
+            i_decimal_separator,
+            0,
+        )
+    }
+
+    /// Creates a deserializer for a whole LVM file, starting with its magic line
+    ///
+    /// The file can then be read with `deserialize_file`.
+    pub fn from_buf_reader(i_reader: R, i_options: &ReadOptions) -> Result<Self> {
+        Deserializer::from_reader(IoReader(i_reader), i_options)
+    }
+}
+
+impl<R: ReadLine> Deserializer<R> {
+    /// Deserializes a whole file, from the file header block to the last measurement
+    ///
+    /// The deserializer must have been created for a whole file, so that the magic line is
+    /// read already.
+    pub fn deserialize_file(&mut self) -> Result<lvm::File> {
+        deserialize_file(self, 0, |_| {})
+    }
+
+    /// Deserializes the measurement packets making up the rest of the input
+    ///
+    /// The input must continue at the start of a packet header or of the blank line before one.
+    pub fn deserialize_measurements(
+        &mut self,
+        i_x_columns: lvm::XColumns,
+    ) -> Result<Vec<lvm::Measurement>> {
+        self.parse_measurements(i_x_columns, true, 0, |_| {})
+    }
+
+    /// Parses data stored one column after another, one value per line, into rows
+    ///
+    /// The x-values come first when the file saves them, followed by each channel's y-values.
+    /// With an x column for every channel, each precedes the channel's y-values.
+    /// Column lengths are taken from the samples of the measurement header.
+    fn parse_columns(
+        &mut self,
+        o_measurement: &mut lvm::Measurement,
+        i_x_columns: lvm::XColumns,
+        i_has_data: bool,
+    ) -> Result<bool> {
+        let samples = &o_measurement.header.samples;
+        let mut lengths = vec![];
+        if i_x_columns == lvm::XColumns::One {
+            lengths.push(samples.iter().cloned().max().unwrap_or(0));
+        }
+        let channels = o_measurement.header.channels.0;
+        let per_channel = if i_x_columns == lvm::XColumns::Multi { 2 } else { 1 };
+        for channel in 0..channels {
+            let length = samples.get(channel).cloned().unwrap_or(0);
+            let end = lengths.len() + per_channel;
+            lengths.resize(end, length);
+        }
+
+        let mut has_data = i_has_data;
+        let mut columns = Vec::with_capacity(lengths.len());
+        let mut raw_columns = vec![];
+        for length in lengths {
+            // Sample counts are not trusted for preallocation, as they come from the input
+            let mut column = vec![];
+            let mut raw_column = vec![];
+            for _ in 0..length {
+                if !has_data {
+                    return self.line_error(ErrorKind::ParseEofUnexpected);
+                }
+                let start = self.line_current_pos;
+                let value = self.parse_real::<lvm::Sample>();
+                column.push(self.line_result(value)?);
+                if self.raw_data {
+                    raw_column.push(self.line_current[start..self.line_current_pos].to_string());
+                }
+                has_data = self.parse_newline_or_eof()?;
+            }
+            columns.push(column);
+            raw_columns.push(raw_column);
+        }
+
+        let rows = columns.iter().map(Vec::len).max().unwrap_or(0);
+        o_measurement.data = (0..rows)
+            .map(|row| {
+                let values = columns
+                    .iter()
+                    .map(|column| column.get(row).cloned().unwrap_or(lvm::Sample::NAN))
+                    .collect();
+                (values, None)
+            })
+            .collect();
+        if self.raw_data {
+            // Missing values have no text, which never reads back as the NaN put in their place
+            o_measurement.raw_data = (0..rows)
+                .map(|row| {
+                    let text = |column: &Vec<String>| column.get(row).cloned().unwrap_or_default();
+                    raw_columns.iter().map(text).collect()
+                })
+                .collect();
+        }
+        Ok(has_data)
+    }
+
+    /// Parses the rest of the line as a data row, if it holds nothing but `i_width`
+    /// tab-separated numbers with a dot decimal separator
+    ///
+    /// Any other row, including a short one or one with a comment or an empty field, is left to
+    /// the general path, which reads it with the same result or reports the error.
+    fn parse_plain_row(&mut self, i_width: usize) -> Option<lvm::DataRow> {
+        let line = &self.line_current[self.line_current_pos..];
+        if line.split('\t').count() != i_width {
+            return None;
+        }
+        let values = line
+            .split('\t')
+            .map(|token| token.parse().ok())
+            .collect::<Option<Vec<lvm::Sample>>>()?;
+        self.line_current_pos = self.line_current.len();
+        Some((values, None))
+    }
+
+    /// Parses a data row of up to `i_width` values, in the current sequence style
+    ///
+    /// The rest of the line following the values and the separator after them is the comment
+    /// of the row, whatever the x columns of the file. Rows cut short, as left by channels
+    /// with fewer samples, have no comment.
+    fn parse_data_row(&mut self, i_width: usize) -> Result<lvm::DataRow> {
+        use serde::de::SeqAccess;
+        let mut values = Vec::with_capacity(i_width);
+        {
+            let mut sequence = self.parse_sequence();
+            while values.len() < i_width {
+                match sequence.next_element::<lvm::Sample>()? {
+                    Some(value) => values.push(value),
+                    None => break,
+                }
+            }
+        }
+        if values.len() < i_width || self.line_is_empty() {
+            return Ok((values, None));
+        }
+        self.parse_separators(1)?;
+        if self.peek_trailing_separator() {
+            return Ok((values, None));
+        }
+        match self.parse_text() {
+            "" => Err(ErrorKind::ParseEolUnexpected.into()),
+            comment => Ok((values, Some(comment.to_string()))),
+        }
+    }
+
+    /// Parses the header block and data headings opening a packet
+    fn parse_packet_header(&mut self, i_x_columns: lvm::XColumns) -> Result<lvm::Measurement> {
+        let mut measurement_header: lvm::MeasurementHeader =
+            self.with_sequence_style(SequenceStyle::Following, Self::deserialize)?;
+        // The separators bound the channel count before values are spread across channels
+        self.parse_separators(measurement_header.channels.0)?;
+        measurement_header.apply_defaults();
+        measurement_header.split_notes(self.separator);
+        self.apply_declared_separator();
+        self.parse_newline()?;
+
+        let measurement = lvm::Measurement {
+            header: measurement_header,
+            data_headings: self.with_sequence_style(
+                SequenceStyle::FollowingSkipLast,
+                Self::deserialize,
+            )?,
+            data: vec![],
+            raw_data: vec![],
+        };
+        self.line_result(measurement.check_headings(i_x_columns))?;
+        Ok(measurement)
+    }
+
+    /// Records the error of a data row and moves past the row, when collecting diagnostics
+    fn skip_row(&mut self, e: Error) -> Result<()> {
+        match self.diagnostics {
+            Some(ref mut diagnostics) => {
+                diagnostics.push((self.line_index, e));
+                self.line_current_pos = self.line_current.len();
+                Ok(())
+            }
+            None => Err(e),
+        }
+    }
+
+    /// Parses a packet, from its header, when it has one, to the end of its data
+    ///
+    /// Each part of the packet is read in its own sequence style, which is restored once the
+    /// part is read, so that none carries over to the next packet.
+    fn parse_packet<F: FnMut(usize)>(
+        &mut self,
+        i_previous: Option<&lvm::Measurement>,
+        i_x_columns: lvm::XColumns,
+        i_multi_headings: bool,
+        o_row_read: &mut F,
+    ) -> Result<lvm::Measurement> {
+        let (mut measurement, has_data) = match i_previous {
+            // Without multiple headings, a packet starting with data reuses the last header
+            Some(previous) if !i_multi_headings && self.peek_data_line(i_x_columns) => {
+                let measurement = lvm::Measurement {
+                    header: previous.header.clone(),
+                    data_headings: previous.data_headings.clone(),
+                    data: vec![],
+                    raw_data: vec![],
+                };
+                (measurement, true)
+            }
+            _ => (self.parse_packet_header(i_x_columns)?, self.parse_newline_or_eof()?),
+        };
+
+        self.parse_padded_data(&mut measurement, i_x_columns, has_data, o_row_read)?;
+        Ok(measurement)
+    }
+
+    /// Parses the data of a packet in the style of its x columns, filling out short rows
+    fn parse_padded_data<F: FnMut(usize)>(
+        &mut self,
+        o_measurement: &mut lvm::Measurement,
+        i_x_columns: lvm::XColumns,
+        i_has_data: bool,
+        o_row_read: &mut F,
+    ) -> Result<()> {
+        let style = match i_x_columns {
+            lvm::XColumns::No => SequenceStyle::Preceding,
+            lvm::XColumns::One | lvm::XColumns::Multi => SequenceStyle::FollowingSkipLast,
+        };
+        self.with_sequence_style(style, |de| {
+            de.parse_data(o_measurement, i_x_columns, i_has_data, o_row_read)
+        })?;
+        // Channels with fewer samples leave the ends of later rows empty
+        let width = o_measurement.row_width(i_x_columns);
+        for &mut (ref mut values, _) in &mut o_measurement.data {
+            if values.len() < width {
+                values.resize(width, lvm::Sample::NAN);
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses the data of a packet up to the blank line or end of file following it
+    ///
+    /// `o_row_read` is called with the line index of every data row read.
+    fn parse_data<F: FnMut(usize)>(
+        &mut self,
+        o_measurement: &mut lvm::Measurement,
+        i_x_columns: lvm::XColumns,
+        i_has_data: bool,
+        o_row_read: &mut F,
+    ) -> Result<()> {
+        let mut has_data = i_has_data;
+        #[cfg(feature = "io")]
+        if let Some(ref mut packet_offsets) = self.packet_offsets {
+            // Reading resumes with the line following the one recorded
+            packet_offsets.push(if has_data {
+                DataOffset {
+                    offset: self.line_offset,
+                    line_index: self.line_index - 1,
+                    separator: self.separator,
+                }
+            } else {
+                DataOffset {
+                    offset: self.offset,
+                    line_index: self.line_index,
+                    separator: self.separator,
+                }
+            });
+            while has_data && !self.peek_newline() {
+                self.line_current_pos = self.line_current.len();
+                has_data = self.parse_newline_or_eof()?;
+            }
+            return Ok(());
+        }
+        if self.column_major {
+            has_data = self.parse_columns(o_measurement, i_x_columns, has_data)?;
+        }
+        // The common layout is worth reading without going through serde for every value
+        let plain_rows = i_x_columns == lvm::XColumns::One
+            && self.separator == '\t'
+            && self.decimal_separator == '.';
+        let width = o_measurement.row_width(i_x_columns);
+        while has_data && !self.peek_newline() {
+            let start = self.line_current_pos;
+            let data_row = match if plain_rows { self.parse_plain_row(width) } else { None } {
+                Some(data_row) => Ok(data_row),
+                None => {
+                    let data_row = self.parse_data_row(width);
+                    self.line_result(data_row)
+                }
+            };
+            let data_row = match data_row {
+                Ok(_) if !self.line_is_empty() => self.line_error(ErrorKind::ParseEolExpected(
+                    self.line_current[self.line_current_pos..].to_string(),
+                )),
+                data_row => data_row,
+            };
+            let data_row = match data_row {
+                Ok(data_row) => data_row,
+                Err(e) => {
+                    self.skip_row(e)?;
+                    has_data = self.parse_newline_or_eof()?;
+                    continue;
+                }
+            };
+            if self.raw_data {
+                // Empty fields hold no value, so the rest line up with the values read
+                let text = &self.line_current[start..self.line_current_pos];
+                let tokens = text.split(self.separator).filter(|token| !token.is_empty());
+                let tokens = tokens.take(data_row.0.len()).map(str::to_string);
+                o_measurement.raw_data.push(tokens.collect());
+            }
+            o_measurement.data.push(data_row);
+            o_row_read(self.line_index);
+            has_data = self.parse_newline_or_eof()?;
+        }
+        Ok(())
+    }
+
+    fn parse_measurements<F: FnMut(usize)>(
+        &mut self,
+        i_x_columns: lvm::XColumns,
+        i_multi_headings: bool,
+        i_progress_interval: usize,
+        mut o_progress: F,
+    ) -> Result<Vec<lvm::Measurement>> {
+        let mut measurements = vec![];
+        let mut rows = 0;
+        let mut row_read = |line| {
+            rows += 1;
+            if i_progress_interval != 0 && rows % i_progress_interval == 0 {
+                o_progress(line);
+            }
+        };
+        while self.parse_newline_or_eof()? {
+            // Lines between packets may hold comments
+            if self.peek_comment_line() && !self.peek_data_line(i_x_columns) {
+                let comment = self.line_current.trim_matches(self.separator).to_string();
+                self.comments.push((self.line_index, comment));
+                self.line_current_pos = self.line_current.len();
+                continue;
+            }
+            if self.peek_newline() {
+                continue;
+            }
+            let measurement = self.parse_packet(
+                measurements.last(),
+                i_x_columns,
+                i_multi_headings,
+                &mut row_read,
+            )?;
+            measurements.push(measurement);
+        }
+        Ok(measurements)
+    }
+}
+
+/// Deserializes the file header block from the specified reader into an arbitrary type
+///
+/// Header keys that the type does not know about are ignored.
+#[cfg(feature = "io")]
+pub fn deserialize_from<'de, T: serde::de::Deserialize<'de>, R: std::io::Read>(
+    i_reader: R,
+) -> Result<T> {
+    let buf_reader = IoReader(std::io::BufReader::new(i_reader));
+    let mut deserializer = Deserializer::from_reader(buf_reader, &ReadOptions::default())?;
+    deserializer.deserialize()
+}
+
+/// Where the data of a packet starts in its file
+#[cfg(feature = "io")]
+#[derive(Clone, Copy, Debug)]
+struct DataOffset {
+    /// Byte offset of the line before the first data row
+    offset: u64,
+    /// 1-based index of the line before the first data row
+    line_index: usize,
+    /// Separator in effect for the data
+    separator: char,
+}
+
+/// An LVM file whose headers are parsed, with the data of each packet loaded on demand
+///
+/// Created by `File::open_indexed`. The file is opened again for every load, so it must not
+/// change in the meantime.
+#[cfg(feature = "io")]
+#[derive(Debug)]
+pub struct IndexedFile {
+    path: std::path::PathBuf,
+    header: lvm::FileHeader,
+    /// Headers and data headings of the measurements, along with where their data starts
+    packets: Vec<(lvm::MeasurementHeader, Vec<String>, DataOffset)>,
+}
+
+#[cfg(feature = "io")]
+impl lvm::File {
+    /// Parses the headers of the file at the specified path, skipping over the data
+    ///
+    /// The byte offset of each packet's data is recorded, so that the data can be read later
+    /// with `IndexedFile::load_measurement_data` without parsing the packets before it.
+    pub fn open_indexed<P: AsRef<std::path::Path>>(path: P) -> Result<IndexedFile> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path)?;
+        let buf_reader = IoReader(std::io::BufReader::new(file));
+        let mut deserializer = Deserializer::from_reader(buf_reader, &ReadOptions::default())?;
+        deserializer.packet_offsets = Some(vec![]);
+        let lvm_file = deserialize_file(&mut deserializer, 0, |_| {})?;
+        let offsets = deserializer.packet_offsets.take().unwrap_or_default();
+        Ok(IndexedFile {
+            path: path.to_path_buf(),
+            header: lvm_file.header,
+            packets: lvm_file
+                .measurements
+                .into_iter()
+                .zip(offsets)
+                .map(|(measurement, offset)| {
+                    (measurement.header, measurement.data_headings, offset)
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(feature = "io")]
+impl IndexedFile {
+    /// Returns the header of the file
+    pub fn header(&self) -> &lvm::FileHeader {
+        &self.header
+    }
+
+    /// Returns the number of measurements in the file
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    /// Returns whether the file holds no measurements
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    /// Returns the header of the measurement at the specified index
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn measurement_header(&self, index: usize) -> &lvm::MeasurementHeader {
+        &self.packets[index].0
+    }
+
+    /// Reads the measurement at the specified index, including its data
+    ///
+    /// Only the data of that measurement is parsed. Errors report the lines they were found on
+    /// within the whole file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn load_measurement_data(&self, index: usize) -> Result<lvm::Measurement> {
+        use std::io::{Seek, SeekFrom};
+        let (ref header, ref data_headings, data_offset) = self.packets[index];
+        let mut file = std::fs::File::open(&self.path)?;
+        file.seek(SeekFrom::Start(data_offset.offset))?;
+        let mut deserializer = Deserializer::with_state(
+            IoReader(std::io::BufReader::new(file)),
+            &ReadOptions::default(),
+            lvm::Separator::try_from(data_offset.separator)?,
+            self.header.decimal_separator(),
+            data_offset.line_index,
+        );
+        let mut measurement = lvm::Measurement {
+            header: header.clone(),
+            data_headings: data_headings.clone(),
+            data: vec![],
+            raw_data: vec![],
+        };
+        let has_data = deserializer.parse_newline_or_eof()?;
+        let x_columns = self.header.x_columns;
+        deserializer.parse_padded_data(&mut measurement, x_columns, has_data, &mut |_| {})?;
+        Ok(measurement)
+    }
+}
+
+/// Serializes LVM file data to a string
+pub fn to_string(i_file: &lvm::File) -> Result<String> {
+    to_string_with_options(i_file, &WriteOptions::default())
+}
+
+/// Serializes LVM file data to a string using the specified options
+pub fn to_string_with_options(i_file: &lvm::File, i_options: &WriteOptions) -> Result<String> {
+    let separator = i_options.separator.unwrap_or_else(|| i_file.header.separator());
+    let mut serializer = Serializer::new(String::new(), separator);
+    serialize_file(&mut serializer, i_file, i_options)?;
+    Ok(serializer.output)
+}
+
+/// Serializes LVM file data to the specified writer
+#[cfg(feature = "io")]
+pub fn to_writer<W: std::io::Write>(i_writer: W, i_file: &lvm::File) -> Result<()> {
+    to_writer_with_options(i_writer, i_file, &WriteOptions::default())
+}
+
+/// Serializes LVM file data to the specified writer using the specified options
+#[cfg(feature = "io")]
+pub fn to_writer_with_options<W: std::io::Write>(
+    i_writer: W,
+    i_file: &lvm::File,
+    i_options: &WriteOptions,
+) -> Result<()> {
+    let separator = i_options.separator.unwrap_or_else(|| i_file.header.separator());
+    let mut serializer = Serializer::new(IoWriter(i_writer), separator);
+    serialize_file(&mut serializer, i_file, i_options)
+}
+
+/// A data value along with its text as read, if retained, and the format to write it in
+#[must_use]
+struct DataCell<'a> {
+    value: lvm::Sample,
+    text: Option<&'a str>,
+    decimal_separator: char,
+    precision: Precision,
+}
+
+impl<'a> serde::ser::Serialize for DataCell<'a> {
+    fn serialize<S: serde::ser::Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+        // The text is only kept while it still stands for the value
+        let text = self.text.filter(|text| {
+            let normalized = normalize_real(text, self.decimal_separator);
+            match normalized.ok().and_then(|t| t.parse::<lvm::Sample>().ok()) {
+                Some(parsed) => parsed == self.value || (parsed.is_nan() && self.value.is_nan()),
+                None => false,
+            }
+        });
+        let formatted = self.precision.format(lvm::widen(self.value)).map(|formatted| {
+            formatted.replace('.', &self.decimal_separator.to_string())
+        });
+        match text {
+            Some(text) => s.serialize_str(text),
+            None => match formatted {
+                Some(formatted) => s.serialize_str(&formatted),
+                None => self.value.serialize(s),
+            },
+        }
+    }
+}
+
+fn serialize_file<W: WriteText>(
+    serializer: &mut Serializer<W>,
+    i_file: &lvm::File,
+    i_options: &WriteOptions,
+) -> Result<()> {
+    serializer.skip_defaults = i_options.skip_defaults;
+    let decimal_separator = i_file.header.decimal_separator();
+    if serializer.separator == char::from(decimal_separator) {
+        let separator = lvm::Separator::try_from(serializer.separator)?;
+        return Err(ErrorKind::SeparatorDecimalCollision(separator).into());
+    }
+    // Raw text is split on the separator read
+    let raw_header =
+        i_options.raw_header && serializer.separator == char::from(i_file.header.separator());
+    match i_file.raw_header {
+        Some(ref raw_header_text) if raw_header => {
+            serializer.write_token(raw_header_text)?;
+            serializer.line_open = !raw_header_text.ends_with('\n');
+            // An edited raw header may have lost its terminator
+            serializer.header_ended = raw_header_text.contains(Serializer::<W>::END_OF_HEADER);
+        }
+        _ => {
+            // Short of the whole raw header, the magic line is re-emitted as read
+            let magic_line = i_file.header.magic_line().filter(|_| raw_header);
+            serializer.write_header(magic_line)?;
+            serializer.serialize(&i_file.header)?;
+        }
+    }
+    serializer.end_header()?;
+    serializer.write_separators(1)?;
+    serializer.write_newline()?;
+    serializer.decimal_separator = decimal_separator.into();
+    let precise = i_options.precision != Precision::Shortest
+        || i_options.column_precision.iter().any(|&precision| precision != Precision::Shortest);
+
+    let mut previous: Option<&lvm::Measurement> = None;
+    for measurement in &i_file.measurements {
+        // Without multiple headings, a packet only needs a header when it differs from the last
+        let repeated = previous.is_some_and(|previous| {
+            !i_file.header.multi_headings()
+                && previous.header == measurement.header
+                && previous.data_headings == measurement.data_headings
+        });
+        if previous.is_some() {
+            serializer.write_newline()?;
+        }
+        previous = Some(measurement);
+        if !repeated {
+            serializer.set_sequence_style(SequenceStyle::Following);
+            serializer.serialize(&measurement.header)?;
+            serializer.write_separators(measurement.header.channels.0)?;
+            serializer.write_newline()?;
+
+            serializer.set_sequence_style(SequenceStyle::FollowingSkipLast);
+            serializer.serialize(&measurement.data_headings)?;
+            serializer.write_newline()?;
+        }
+
+        serializer.set_sequence_style(match i_file.header.x_columns {
+            lvm::XColumns::No => SequenceStyle::Preceding,
+            lvm::XColumns::One | lvm::XColumns::Multi => SequenceStyle::FollowingSkipLast,
+        });
+        for (row, (values, comment)) in measurement.data.iter().enumerate() {
+            let raw_row = measurement.raw_data.get(row);
+            if raw_row.is_some() || precise {
+                let decimal_separator = serializer.decimal_separator;
+                let cells: Vec<DataCell> = values
+                    .iter()
+                    .enumerate()
+                    .map(|(index, &value)| DataCell {
+                        value,
+                        text: raw_row
+                            .and_then(|raw_row| raw_row.get(index))
+                            .map(String::as_str),
+                        decimal_separator,
+                        precision: i_options
+                            .column_precision
+                            .get(index)
+                            .cloned()
+                            .unwrap_or(i_options.precision),
+                    })
+                    .collect();
+                serializer.serialize(&cells)?;
+            } else {
+                serializer.serialize(values)?;
+            }
+            if let Some(comment) = comment {
+                serializer.write_separators(1)?;
+                serializer.write_token(comment)?;
+            }
+            serializer.write_newline()?;
+        }
+    }
+
+    serializer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = "LabVIEW Measurement\t\n\
+                        Writer_Version\t2\n\
+                        Reader_Version\t2\n\
+                        Separator\tTab\n\
+                        Decimal_Separator\t.\n\
+                        Multi_Headings\tNo\n\
+                        X_Columns\tOne\n\
+                        Time_Pref\tRelative\n\
+                        Operator\tOperator\n\
+                        Date\t2018/02/03\n\
+                        Time\t12:34:56.789\n\
+                        ***End_of_Header***\t\n\
+                        \t\n\
+                        Channels\t2\t\n\
+                        Samples\t3\t3\t\n\
+                        Date\t2018/02/03\t2018/02/03\t\n\
+                        Time\t12:34:56.789\t12:34:56.789\t\n\
+                        X_Dimension\tTime\tTime\t\n\
+                        X0\t0.0\t0.0\t\n\
+                        Delta_X\t0.001\t0.001\t\n\
+                        ***End_of_Header***\t\t\t\n\
+                        X_Value\tUntitled\tUntitled 1\tComment\n\
+                        0.000\t1.0\t2.0\n\
+                        0.001\t1.1\t2.1\n\
+                        0.002\t1.2\t2.2\n";
+
+    /// `FILE` split on commas throughout, declaring its separator by name
+    const COMMA_FILE: &str = "LabVIEW Measurement,\n\
+                              Writer_Version,2\n\
+                              Reader_Version,2\n\
+                              Separator,Comma\n\
+                              Decimal_Separator,.\n\
+                              Multi_Headings,No\n\
+                              X_Columns,One\n\
+                              Time_Pref,Relative\n\
+                              Operator,Operator\n\
+                              Date,2018/02/03\n\
+                              Time,12:34:56.789\n\
+                              ***End_of_Header***,\n\
+                              ,\n\
+                              Channels,2,\n\
+                              Samples,3,3,\n\
+                              Date,2018/02/03,2018/02/03,\n\
+                              Time,12:34:56.789,12:34:56.789,\n\
+                              X_Dimension,Time,Time,\n\
+                              X0,0.0,0.0,\n\
+                              Delta_X,0.001,0.001,\n\
+                              ***End_of_Header***,,,\n\
+                              X_Value,Untitled,Untitled 1,Comment\n\
+                              0.000,1.0,2.0\n\
+                              0.001,1.1,2.1\n\
+                              0.002,1.2,2.2\n";
+
+    /// Compares values within the precision of the narrowest `Sample` type
+    fn assert_close(i_values: &[f64], i_expected: &[f64]) {
+        assert_eq!(i_values.len(), i_expected.len(), "{:?} != {:?}", i_values, i_expected);
+        for (value, expected) in i_values.iter().zip(i_expected) {
+            let tolerance = f64::from(f32::EPSILON) * expected.abs().max(1.0);
+            assert!((value - expected).abs() <= tolerance, "{} != {}", value, expected);
+        }
+    }
+
+    fn deserializer(i_text: &str) -> Deserializer<&str> {
+        Deserializer::from_reader(i_text, &ReadOptions::default()).unwrap()
+    }
+
+    /// Returns a path in the temporary directory unique to this process and call
+    #[cfg(feature = "io")]
+    fn temp_path(i_name: &str) -> std::path::PathBuf {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let count = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let name = format!("serde_lvm_{}_{}_{}.lvm", i_name, std::process::id(), count);
+        std::env::temp_dir().join(name)
+    }
+
+    #[test]
+    fn integer_128() {
+        #[derive(Deserialize)]
+        struct Ticks {
+            #[serde(rename = "Signed")]
+            signed: i128,
+            #[serde(rename = "Unsigned")]
+            unsigned: u128,
+        }
+
+        let mut d = deserializer(
+            "LabVIEW Measurement\t\n\
+             Signed\t-170141183460469231731687303715884105728\n\
+             Unsigned\t340282366920938463463374607431768211455\n\
+             ***End_of_Header***\t\n",
+        );
+        let ticks: Ticks = d.deserialize().unwrap();
+        assert_eq!(ticks.signed, i128::MIN);
+        assert_eq!(ticks.unsigned, u128::MAX);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn x_columns_numeric() {
+        let file = from_reader(FILE.replace("X_Columns\tOne", "X_Columns\t1").as_bytes()).unwrap();
+        match file.header.x_columns {
+            lvm::XColumns::One => {}
+            x => panic!("unexpected {:?}", x),
+        }
+        assert_eq!(file.measurements[0].data.len(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn separator_decimal_collision() {
+        let text = FILE.replace("Separator\tTab", "Separator\tComma")
+            .replace("Decimal_Separator\t.", "Decimal_Separator\t,");
+        match from_reader(text.as_bytes()) {
+            Err(Error(ErrorKind::SeparatorDecimalCollision(lvm::Separator::Comma), _)) => {}
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn raw_header_reemission() {
+        let header_end = FILE.find("***End_of_Header***").unwrap();
+        let header = &FILE[..header_end + FILE[header_end..].find('\n').unwrap() + 1];
+        let text = FILE.replace('\n', "\r\n");
+        let header = header.replace('\n', "\r\n");
+
+        let read_options = ReadOptions {
+            raw_header: true,
+            ..ReadOptions::default()
+        };
+        let file = from_reader_with_options(text.as_bytes(), &read_options).unwrap();
+        assert_eq!(file.raw_header.as_ref(), Some(&header));
+
+        let write_options = WriteOptions {
+            raw_header: true,
+            ..WriteOptions::default()
+        };
+        let mut output = vec![];
+        to_writer_with_options(&mut output, &file, &write_options).unwrap();
+        assert!(output.starts_with(header.as_bytes()));
+        assert_eq!(from_reader(&output[..]).unwrap().measurements.len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn magic_line() {
+        let file = from_str(FILE).unwrap();
+        assert_eq!(file.header.magic_line(), Some("LabVIEW Measurement\t"));
+        assert_eq!(from_str(&FILE.replace("\n", "\r\n")).unwrap().header, file.header);
+
+        let text = FILE.replacen("LabVIEW Measurement\t", "LabVIEW Measurement\t;", 1);
+        let file = from_reader_with_separator(text.as_bytes(), lvm::Separator::Tab).unwrap();
+        assert_eq!(file.header.magic_line(), Some("LabVIEW Measurement\t;"));
+        assert!(to_string(&file).unwrap().starts_with("LabVIEW Measurement\t\n"));
+        let options = WriteOptions {
+            raw_header: true,
+            ..WriteOptions::default()
+        };
+        let written = to_string_with_options(&file, &options).unwrap();
+        assert!(written.starts_with("LabVIEW Measurement\t;\nDate\t"));
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn writer_roundtrip() {
+        let mut first = vec![];
+        to_writer(&mut first, &from_reader(FILE.as_bytes()).unwrap()).unwrap();
+        let mut second = vec![];
+        to_writer(&mut second, &from_reader(&first[..]).unwrap()).unwrap();
+        assert_eq!(String::from_utf8(first).unwrap(), String::from_utf8(second).unwrap());
+    }
+
+    /// Checks that strings can still be read and written without the `io` feature
+    #[test]
+    #[cfg(not(feature = "io"))]
+    fn string_roundtrip_without_io() {
+        let first = to_string(&from_str(FILE).unwrap()).unwrap();
+        assert_eq!(first, to_string(&from_str(&first).unwrap()).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn roundtrip_helper() {
+        let path = temp_path("roundtrip_helper");
+        std::fs::write(&path, FILE).unwrap();
+        ::tests::assert_roundtrip(&path);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn y_dimension_per_channel() {
+        let file = from_reader(FILE.as_bytes()).unwrap();
+        assert_eq!(
+            file.measurements[0].header.y_dimension,
+            vec![lvm::UnitType::ElectricPotential; 2]
+        );
+
+        let text = FILE.replace("X0\t", "Y_Dimension\tElectric_Potential\tTime\t\nX0\t");
+        let file = from_reader(text.as_bytes()).unwrap();
+        assert_eq!(
+            file.measurements[0].header.y_dimension,
+            vec![lvm::UnitType::ElectricPotential, lvm::UnitType::Time]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn unknown_enum_values() {
+        let text = FILE.replace("Time_Pref\tRelative", "Time_Pref\tAbsolute_UTC")
+            .replace("X_Dimension\tTime\tTime", "X_Dimension\tTime\tFrequency");
+        let file = from_reader(text.as_bytes()).unwrap();
+        assert_eq!(
+            file.header.time_pref,
+            lvm::TimePref::Other("Absolute_UTC".to_string())
+        );
+        assert_eq!(
+            file.measurements[0].header.x_dimension,
+            Some(vec![
+                lvm::UnitType::Time,
+                lvm::UnitType::Other("Frequency".to_string()),
+            ])
+        );
+
+        // Values the layout depends on cannot degrade, so are reported on their line
+        let cases = [
+            ("X_Columns\tOne", "X_Columns\tTwo", 7),
+            ("Separator\tTab", "Separator\tSemicolon", 4),
+            ("Decimal_Separator\t.", "Decimal_Separator\t;", 5),
+        ];
+        for &(from, to, line) in &cases {
+            let error = from_str(&FILE.replace(from, to)).unwrap_err();
+            assert_eq!(error.line(), Some(line), "{}", to);
+            let value = &to[to.find('\t').unwrap() + 1..];
+            let found = format!("\"{}\" was found", value);
+            assert!(error.iter().any(|e| e.to_string().contains(&found)), "{}", to);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn column_by_name() {
+        let file = from_reader(FILE.as_bytes()).unwrap();
+        let measurement = &file.measurements[0];
+        assert_eq!(measurement.column_by_name("Untitled"), Some(vec![1.0, 1.1, 1.2]));
+        assert_eq!(measurement.column_by_name("Untitled 1"), Some(vec![2.0, 2.1, 2.2]));
+        assert_eq!(measurement.column_by_name("Comment"), None);
+
+        let text = FILE.replace("Untitled 1", "Untitled");
+        let file = from_reader(text.as_bytes()).unwrap();
+        assert_eq!(file.measurements[0].column_by_name("Untitled"), Some(vec![1.0, 1.1, 1.2]));
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn deserialize_custom_header() {
+        #[derive(Deserialize)]
+        struct Header {
+            #[serde(rename = "Operator")]
+            operator: String,
+            #[serde(rename = "Date")]
+            date: lvm::Date,
+            #[serde(rename = "Project")]
+            project: Option<String>,
+        }
+
+        let header: Header = deserialize_from(FILE.as_bytes()).unwrap();
+        assert_eq!(header.operator, "Operator");
+        assert_eq!(header.date.to_string(), "2018/02/03");
+        assert_eq!(header.project, None);
+    }
+
+    #[test]
+    fn unsupported_types() {
+        #[derive(Debug, Deserialize)]
+        struct Header {
+            #[serde(rename = "Operator")]
+            _operator: char,
+        }
+
+        let is_unsupported = |kind: &ErrorKind| match *kind {
+            ErrorKind::Unsupported(what) => Some(what),
+            _ => None,
+        };
+        let error = deserializer(FILE).deserialize::<Header>().unwrap_err();
+        assert_eq!(error.find_innermost(is_unsupported), Some("Reading a char"));
+        let error = deserializer(FILE)
+            .deserialize::<std::collections::HashMap<String, String>>()
+            .unwrap_err();
+        assert_eq!(error.find_innermost(is_unsupported), Some("Reading a map"));
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn waveforms() {
+        let file = from_reader(FILE.as_bytes()).unwrap();
+        let measurement = &file.measurements[0];
+        assert_eq!(
+            measurement.waveform(1),
+            Some(lvm::Waveform {
+                x0: 0.0,
+                delta_x: 0.001,
+                samples: 3,
+                data: vec![2.0, 2.1, 2.2],
+            })
+        );
+        assert_eq!(measurement.waveform(2), None);
+        assert_eq!(measurement.waveforms().count(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn delta_x_precision() {
+        let text = FILE.replace("Delta_X\t0.001\t", "Delta_X\t0.000000001000000001\t");
+        let file = from_reader(text.as_bytes()).unwrap();
+        assert_eq!(file.measurements[0].header.delta_x[0], 0.000000001000000001);
+    }
+
+    #[test]
+    fn string_roundtrip() {
+        let file = from_str(FILE).unwrap();
+        let text = to_string(&file).unwrap();
+        assert_eq!(to_string(&from_str(&text).unwrap()).unwrap(), text);
+    }
+
+    #[test]
+    fn trailing_separator() {
+        let text = ["2.0", "2.1", "2.2"].iter().fold(FILE.to_string(), |text, value| {
+            text.replace(&format!("{}\n", value), &format!("{}\t\n", value))
+        });
+        assert!(from_str(&text).is_err());
+
+        let options = ReadOptions {
+            lenient: true,
+            ..ReadOptions::default()
+        };
+        let file = from_str_with_options(&text, &options).unwrap();
+        assert_eq!(file.measurements[0].data.len(), 3);
+        assert_eq!(file.measurements[0].data[0], (vec![0.0, 1.0, 2.0], None));
+    }
+
+    #[test]
+    fn comment_column() {
+        let file = from_str(FILE).unwrap();
+        assert!(file.measurements[0].has_comment_column());
+
+        let file = from_str(&FILE.replace("\tComment\n", "\n")).unwrap();
+        assert!(!file.measurements[0].has_comment_column());
+    }
+
+    #[test]
+    fn embedded_units() {
+        let text = FILE.replace("X_Dimension\tTime\tTime", "X_Dimension\tTime (s)\tTime (ms)");
+        let file = from_str(&text).unwrap();
+        let header = &file.measurements[0].header;
+        assert_eq!(
+            header.x_dimension,
+            Some(vec![lvm::UnitType::Time, lvm::UnitType::Time])
+        );
+        assert_eq!(
+            header.x_unit_label,
+            Some(vec![
+                lvm::Unit::Other("s".to_string()),
+                lvm::Unit::Other("ms".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn sample_rate() {
+        let text = FILE.replace("Delta_X\t0.001\t0.001", "Delta_X\t0.001\t0");
+        let file = from_str(&text).unwrap();
+        let header = &file.measurements[0].header;
+        assert_eq!(header.sample_rate(0), Some(1000.0));
+        assert_eq!(header.sample_rate(1), None);
+        assert_eq!(header.sample_rate(2), None);
+    }
+
+    #[test]
+    fn interval() {
+        let text = FILE.replace("X0\t", "Interval\t0.5\t0.25\t\nUnknown_Key\tValue\t\nX0\t");
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.measurements[0].header.interval, Some(vec![0.5, 0.25]));
+        assert_eq!(from_str(FILE).unwrap().measurements[0].header.interval, None);
+    }
+
+    #[test]
+    fn error_location() {
+        let text = FILE.replace("Time_Pref\tRelative", "Time_Pref\tRelative\tB");
+        let error = from_str(&text).unwrap_err();
+        assert_eq!(error.line(), Some(8));
+        assert_eq!(error.column(), Some(19));
+
+        let error = from_str(&FILE.replace("Separator\tTab", "Separator\tComma")
+            .replace("Decimal_Separator\t.", "Decimal_Separator\t,"))
+            .unwrap_err();
+        assert_eq!(error.line(), None);
+        assert_eq!(error.column(), None);
+    }
+
+    #[test]
+    fn separator_redeclared() {
+        let end = FILE.rfind("***End_of_Header***").unwrap();
+        let data = end + FILE[end..].find('\n').unwrap() + 1;
+        let text = format!(
+            "{}Separator\tComma\n{}{}",
+            &FILE[..end],
+            &FILE[end..data],
+            FILE[data..].replace('\t', ",")
+        );
+        let file = from_str(&text).unwrap();
+        let measurement = &file.measurements[0];
+        assert_eq!(measurement.data_headings[1], "Untitled");
+        assert_eq!(measurement.data[2].0, vec![0.002, 1.2, 2.2]);
+
+        let padded = text.replace("Separator\tComma\n", "Separator\tComma\t\t\n");
+        assert_eq!(from_str(&padded).unwrap().measurements, file.measurements);
+    }
+
+    #[test]
+    fn empty_packets() {
+        let packet = &FILE[FILE.find("Channels").unwrap()..];
+        let empty = &packet[..packet.find("0.000").unwrap()];
+
+        let file = from_str(&format!("{}\n{}\n{}", FILE, empty, packet)).unwrap();
+        let lengths: Vec<_> = file.measurements.iter().map(|m| m.data.len()).collect();
+        assert_eq!(lengths, vec![3, 0, 3]);
+
+        let file = from_str(&format!("{}\n{}", FILE, empty)).unwrap();
+        let lengths: Vec<_> = file.measurements.iter().map(|m| m.data.len()).collect();
+        assert_eq!(lengths, vec![3, 0]);
+    }
+
+    #[test]
+    fn separator_from_str() {
+        for &(text, expected) in &[
+            (",", ','),
+            ("\t", '\t'),
+            ("comma", ','),
+            ("Comma", ','),
+            ("tab", '\t'),
+            ("TAB", '\t'),
+        ] {
+            assert_eq!(char::from(lvm::Separator::try_from(text).unwrap()), expected);
+        }
+        assert!(lvm::Separator::try_from(";").is_err());
+    }
+
+    #[test]
+    fn escaped_separator() {
+        for &(escape, expected) in &[
+            ("\\t", '\t'),
+            ("\\0x09", '\t'),
+            ("\\0x2C", ','),
+            ("\\x2c", ','),
+            ("0x2C", ','),
+        ] {
+            assert_eq!(char::from(lvm::Separator::try_from(escape).unwrap()), expected);
+        }
+        assert!(lvm::Separator::try_from("\\0x3B").is_err());
+        assert!(lvm::Separator::try_from("\\0x").is_err());
+
+        let text = FILE.replace("Separator\tTab", "Separator\t\\0x09");
+        assert_eq!(from_str(&text).unwrap(), from_str(FILE).unwrap());
+
+        let comma = FILE.replace('\t', ",");
+        let text = comma.replace("Separator,Tab", "Separator,\\0x2C");
+        let comma = comma.replace("Separator,Tab", "Separator,Comma");
+        let file = from_str(&text).unwrap();
+        assert_eq!(file, from_str(&comma).unwrap());
+        assert_eq!(file.header.separator(), lvm::Separator::Comma);
+    }
+
+    #[test]
+    fn separator_names() {
+        let file = from_str(COMMA_FILE).unwrap();
+        assert_eq!(file.header.separator(), lvm::Separator::Comma);
+        assert_eq!(file.header.magic_separator(), lvm::Separator::Comma);
+        assert_eq!(file.measurements[0].data[2].0, vec![0.002, 1.2, 2.2]);
+        let text = to_string(&file).unwrap();
+        assert!(text.starts_with("LabVIEW Measurement,\n"));
+        assert!(text.contains("\nSeparator,Comma\n"));
+        assert_eq!(from_str(&text).unwrap(), file);
+
+        // The magic line splits the file header, and the field the rest of the file
+        let header_end = |text: &str| {
+            let end = text.find("***End_of_Header***").unwrap();
+            end + text[end..].find('\n').unwrap() + 1
+        };
+        let tab_data = |header: &str| {
+            format!("{}{}", &header[..header_end(header)], &FILE[header_end(FILE)..])
+        };
+        for &(name, expected) in &[("comma", COMMA_FILE), ("COMMA", COMMA_FILE), ("tab", FILE)] {
+            let text = COMMA_FILE.replace("Separator,Comma", &format!("Separator,{}", name));
+            let text = if expected == FILE { tab_data(&text) } else { text };
+            let file = from_str(&text).unwrap();
+            assert_eq!(file.measurements, from_str(expected).unwrap().measurements);
+        }
+
+        let file = from_str(&tab_data(&COMMA_FILE.replace("Separator,Comma", "Separator,Tab")))
+            .unwrap();
+        assert_eq!(file.header.separator(), lvm::Separator::Tab);
+        assert_eq!(file.header.magic_separator(), lvm::Separator::Comma);
+        let text = to_string(&file).unwrap();
+        assert!(text.starts_with("LabVIEW Measurement\t\n"));
+        assert!(text.contains("\nSeparator\tTab\n"));
+        assert_eq!(from_str(&text).unwrap().measurements, file.measurements);
+    }
+
+    #[test]
+    fn version_comparison() {
+        let file = from_str(FILE).unwrap();
+        let version = &file.header.writer_version;
+        assert!(version.is_v2());
+        assert!(version.at_least("2.0"));
+        assert!(version.at_least("2"));
+        assert!(version.at_least("1.5"));
+        assert!(!version.at_least("2.1"));
+        assert!(!version.at_least("3"));
+        assert!(!version.at_least("two"));
+
+        let version: lvm::Version = "1.0".parse().unwrap();
+        assert!(!version.is_v2());
+        assert!(version.at_least("0.9"));
+        assert!(!version.at_least("1.0.1"));
+        assert!("2.3".parse::<lvm::Version>().unwrap().is_v2());
+    }
+
+    #[test]
+    fn bool_spellings() {
+        let text = FILE.replace("Multi_Headings\tNo", "Multi_Headings\tTrue");
+        let file = from_str(&text).unwrap();
+        assert!(file.header.multi_headings());
+        assert!(to_string(&file).unwrap().contains("\nMulti_Headings\tYes\n"));
+
+        for &(spelling, expected) in &[
+            ("true", true),
+            ("FALSE", false),
+            ("1", true),
+            ("0", false),
+            ("yes", true),
+            ("no", false),
+        ] {
+            let text = FILE.replace("Multi_Headings\tNo", &format!("Multi_Headings\t{}", spelling));
+            assert_eq!(from_str(&text).unwrap().header.multi_headings(), expected, "{}", spelling);
+        }
+        let text = FILE.replace("Multi_Headings\tNo", "Multi_Headings\tMaybe");
+        assert!(from_str(&text).is_err());
+    }
+
+    #[test]
+    fn transcoding() {
+        let text = COMMA_FILE
+            .replace(",Untitled 1,", ",\"Untitled 1, A\",")
+            .replace("Operator,Operator", "Operator,Doe, Jane")
+            .replace("0.001,1.1,2.1", "0.001,1.1,2.1,spike, large");
+        let file = from_str(&text).unwrap();
+        let options = WriteOptions {
+            separator: Some(lvm::Separator::Tab),
+            raw_header: true,
+            ..Default::default()
+        };
+        let tab = to_string_with_options(&file, &options).unwrap();
+        assert!(tab.starts_with("LabVIEW Measurement\t\n"));
+        assert!(tab.contains("\nSeparator\tTab\n"));
+        assert!(tab.contains("\tUntitled 1, A\t"));
+        assert!(tab.contains("\tspike, large\n"));
+        let reread = from_str(&tab).unwrap();
+        assert_eq!(reread.header.separator(), lvm::Separator::Tab);
+        assert_eq!(reread.header.operator, file.header.operator);
+        assert_eq!(reread.measurements, file.measurements);
+
+        let options = WriteOptions {
+            separator: Some(lvm::Separator::Comma),
+            ..Default::default()
+        };
+        let comma = to_string_with_options(&reread, &options).unwrap();
+        assert!(comma.contains(",\"Untitled 1, A\","));
+        assert_eq!(from_str(&comma).unwrap().measurements, file.measurements);
+
+        let headings = FILE.find("X_Value").unwrap();
+        let text = FILE[..headings]
+            .replace("Decimal_Separator\t.", "Decimal_Separator\t,")
+            .replace("\t0.0", "\t0,0") + &FILE[headings..].replace('.', ",");
+        let error = to_string_with_options(&from_str(&text).unwrap(), &options).unwrap_err();
+        match *error.kind() {
+            ErrorKind::SeparatorDecimalCollision(lvm::Separator::Comma) => {}
+            ref kind => panic!("{:?}", kind),
+        }
+    }
+
+    #[test]
+    fn separator_detection() {
+        let detect = lvm::Separator::detect_from_line;
+        assert_eq!(detect("X_Value\tUntitled\tUntitled 1, A\tComment"), Some(lvm::Separator::Tab));
+        assert_eq!(detect("0.000,1.0,2.0"), Some(lvm::Separator::Comma));
+        assert_eq!(detect("Operator,Jane\tDoe"), Some(lvm::Separator::Tab));
+        assert_eq!(detect("0,000;1,0;2,0;3,0"), None);
+        assert_eq!(detect("Channels;2;Untitled"), None);
+        assert_eq!(detect("Notes,see A; B,1,2"), Some(lvm::Separator::Comma));
+        assert_eq!(detect("***End_of_Header***"), None);
+        for line in FILE.lines().filter(|line| !line.is_empty()) {
+            assert_eq!(detect(line), Some(lvm::Separator::Tab), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn decimal_separator_default() {
+        let text = FILE.replace("Decimal_Separator\t.\n", "")
+            .replace("_Version\t2", "_Version\t1");
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.header.decimal_separator(), lvm::DecimalSeparator::Dot);
+        assert_eq!(file.measurements[0].data.len(), 3);
+    }
+
+    #[test]
+    fn scalar_samples() {
+        let text = FILE.replace("Samples\t3\t3\t\n", "Samples\t3\t\n");
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.measurements[0].header.samples, vec![3, 3]);
+        assert_eq!(file.measurements[0].data.len(), 3);
+    }
+
+    #[test]
+    fn channel_view() {
+        let file = from_str(FILE).unwrap();
+        let channel = file.measurements[0].channel(1);
+        assert_eq!(channel.index(), 1);
+        assert_eq!(channel.heading(), Some("Untitled 1"));
+        assert_eq!(channel.data(), Some(vec![2.0, 2.1, 2.2]));
+        assert!(file.measurements[0].get_channel(2).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "the number of channels is 2 but the index is 2")]
+    fn channel_view_out_of_bounds() {
+        let file = from_str(FILE).unwrap();
+        let _ = file.measurements[0].channel(2);
+    }
+
+    #[test]
+    fn text_with_separator() {
+        let text = FILE.replace("Operator\tOperator", "Operator\tJane, Lab 3\tBench 2");
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.header.operator.unwrap().as_str(), "Jane, Lab 3\tBench 2");
+
+        let text = FILE
+            .replace('\t', ",")
+            .replace("Separator,Tab", "Separator,Comma")
+            .replace("Operator,Operator", "Operator,Jane, Lab 3");
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.header.operator.unwrap().as_str(), "Jane, Lab 3");
+    }
+
+    #[test]
+    fn version_compatibility() {
+        let file = from_str(FILE).unwrap();
+        assert!(file.header.is_compatible());
+
+        let text = FILE.replace("Reader_Version\t2", "Reader_Version\t1");
+        assert!(from_str(&text).unwrap().header.is_compatible());
+
+        let text = FILE
+            .replace("Writer_Version\t2", "Writer_Version\t3.1")
+            .replace("Reader_Version\t2", "Reader_Version\t2.1");
+        let file = from_str(&text).unwrap();
+        assert!(!file.header.is_compatible());
+    }
+
+    #[test]
+    fn quoted_headings() {
+        let text = FILE
+            .replace('\t', ",")
+            .replace("Separator,Tab", "Separator,Comma")
+            .replace("Untitled 1,", "\"Voltage, \"\"raw\"\"\",");
+        let file = from_str(&text).unwrap();
+        let headings = &file.measurements[0].data_headings;
+        assert_eq!(headings[2], "Voltage, \"raw\"");
+        assert_eq!(headings[3], "Comment");
+
+        let written = to_string(&file).unwrap();
+        assert!(written.contains("\"Voltage, \"\"raw\"\"\","));
+        assert_eq!(from_str(&written).unwrap(), file);
+    }
+
+    #[test]
+    fn into_rows_and_columns() {
+        let measurement = from_str(FILE).unwrap().measurements.remove(0);
+        let data = measurement.data.as_ptr();
+        let rows = measurement.into_rows();
+        assert_eq!(rows.as_ptr(), data);
+        assert_eq!(rows[1], (vec![0.001, 1.1, 2.1], None));
+
+        let columns = from_str(FILE).unwrap().measurements.remove(0).into_columns();
+        assert_eq!(
+            columns,
+            vec![
+                vec![0.000, 0.001, 0.002],
+                vec![1.0, 1.1, 1.2],
+                vec![2.0, 2.1, 2.2],
+            ]
+        );
+        assert!(columns.iter().all(|column| column.capacity() == column.len()));
+    }
+
+    #[test]
+    fn heading_count_mismatch() {
+        let text = FILE.replace("X_Value\tUntitled\tUntitled 1", "X_Value\tUntitled");
+        let error = from_str(&text).unwrap_err();
+        assert_eq!(error.line(), Some(22));
+        assert!(error
+            .iter()
+            .any(|e| e.to_string() == "1 data headings were found for 2 channels"));
+
+        let text = FILE.replace("\tComment\n", "\n");
+        assert!(from_str(&text).is_ok());
+    }
+
+    #[test]
+    fn decimal_comma() {
+        let headings = FILE.find("X_Value").unwrap();
+        let text = FILE[..headings]
+            .replace("Decimal_Separator\t.", "Decimal_Separator\t,")
+            .replace("\t0.0", "\t0,0") + &FILE[headings..]
+            .replace('.', ",")
+            .replace("1,1\t", "1.234,56\t");
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.measurements[0].header.delta_x, vec![0.001, 0.001]);
+        assert_eq!(file.measurements[0].data[1].0, vec![0.001, 1234.56, 2.1]);
+
+        let written = to_string(&file).unwrap();
+        assert!(written.contains("\n0,001\t1234,56\t"));
+        assert_eq!(from_str(&written).unwrap(), file);
+
+        let is_invalid_grouping = |kind: &ErrorKind| match *kind {
+            ErrorKind::InvalidGrouping(ref token) => Some(token.clone()),
+            _ => None,
+        };
+        for token in &["1.5", "1.234.5", ".234", "1.234,5.6", "1234.567"] {
+            let error = from_str(&text.replace("1.234,56", token)).unwrap_err();
+            assert_eq!(error.find_innermost(is_invalid_grouping), Some(token.to_string()));
+        }
+        let grouped = text.replace("1.234,56", "-1.234.567,5");
+        assert_eq!(from_str(&grouped).unwrap().measurements[0].data[1].0[1], -1234567.5);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn progress() {
+        let mut lines = vec![];
+        let file = from_reader_with_progress(FILE.as_bytes(), &ReadOptions::default(), 1, |line| {
+            lines.push(line)
+        }).unwrap();
+        assert_eq!(file.measurements[0].data.len(), 3);
+        assert_eq!(lines, vec![23, 24, 25]);
+
+        let mut calls = 0;
+        let file =
+            from_reader_with_progress(FILE.as_bytes(), &ReadOptions::default(), 2, |_| calls += 1)
+                .unwrap();
+        assert_eq!(file.measurements[0].data.len(), 3);
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn enum_index() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Mode {
+            Single,
+            Continuous,
+        }
+
+        #[derive(Deserialize)]
+        struct Header {
+            #[serde(rename = "Mode")]
+            mode: Mode,
+        }
+
+        let text = FILE.replace("Operator\tOperator", "Mode\t1");
+        let header: Header = deserialize_from(text.as_bytes()).unwrap();
+        assert_eq!(header.mode, Mode::Continuous);
+
+        let text = FILE.replace("Operator\tOperator", "Mode\tSingle");
+        let header: Header = deserialize_from(text.as_bytes()).unwrap();
+        assert_eq!(header.mode, Mode::Single);
+
+        let text = FILE.replace("Operator\tOperator", "Mode\t2");
+        assert!(deserialize_from::<Header, _>(text.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn iterate_measurements() {
+        let packet = &FILE[FILE.find("Channels").unwrap()..];
+        let second = packet
+            .replace("\t3\t3\t", "\t2\t2\t")
+            .replace("0.002\t1.2\t2.2\n", "");
+        let text = format!("{}\n{}", FILE, second);
+        let file = from_str(&text).unwrap();
+        file.validate_all().unwrap();
+
+        let mut samples = vec![];
+        for measurement in &file {
+            samples.push(measurement.header.samples[0]);
+        }
+        assert_eq!(samples, vec![3, 2]);
+
+        let mut lengths = vec![];
+        for measurement in file {
+            lengths.push(measurement.data.len());
+        }
+        assert_eq!(lengths, vec![3, 2]);
+    }
+
+    #[test]
+    fn y_scaling() {
+        let file = from_str(FILE).unwrap();
+        assert_eq!(file.measurements[0].header.y0, None);
+        assert_close(&file.measurements[0].scaled_column(0).unwrap(), &[1.0, 1.1, 1.2]);
+
+        let text = FILE.replace("X0\t", "Y0\t1.0\t-1.0\t\nY_Scale\t2.0\t10.0\t\nX0\t");
+        let file = from_str(&text).unwrap();
+        let measurement = &file.measurements[0];
+        assert_eq!(measurement.header.y_scale, Some(vec![2.0, 10.0]));
+        assert_close(&measurement.scaled_column(0).unwrap(), &[3.0, 3.2, 3.4]);
+        assert_close(&measurement.scaled_column(1).unwrap(), &[19.0, 20.0, 21.0]);
+        assert_eq!(from_str(&to_string(&file).unwrap()).unwrap(), file);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn headerless_body() {
+        let body = &FILE[FILE.find("Channels").unwrap()..];
+        let mut deserializer = Deserializer::new(
+            body.as_bytes(),
+            lvm::Separator::Tab,
+            lvm::DecimalSeparator::Dot,
+            &ReadOptions::default(),
+        );
+        let measurements = deserializer.deserialize_measurements(lvm::XColumns::One).unwrap();
+        assert_eq!(measurements.len(), 1);
+        assert_eq!(measurements[0].header.samples, vec![3, 3]);
+        assert_eq!(measurements[0].data[2].0, vec![0.002, 1.2, 2.2]);
+
+        let body = body.replace("Samples\t3", "Samples\tx");
+        let mut deserializer = Deserializer::new(
+            body.as_bytes(),
+            lvm::Separator::Tab,
+            lvm::DecimalSeparator::Dot,
+            &ReadOptions::default(),
+        );
+        let error = deserializer.deserialize_measurements(lvm::XColumns::One).unwrap_err();
+        assert_eq!(error.line(), Some(2));
+    }
+
+    #[test]
+    fn header_blank_and_comment_lines() {
+        let text = FILE
+            .replace("Operator\t", "\n# edited by hand\nOperator\t")
+            .replace("X0\t", "\nX0\t");
+        let options = ReadOptions {
+            lenient: true,
+            ..ReadOptions::default()
+        };
+        let file = from_str_with_options(&text, &options).unwrap();
+        assert_eq!(file.header.operator.unwrap().as_str(), "Operator");
+        assert_eq!(file.measurements[0].header.x0, vec![0.0, 0.0]);
+        assert_eq!(file.measurements[0].data.len(), 3);
+    }
+
+    #[test]
+    fn time_base() {
+        let text = FILE.replace("X0\t0.0\t0.0\t", "X0\t0.5\t");
+        let file = from_str(&text).unwrap();
+        let header = &file.measurements[0].header;
+        assert_eq!(header.time_base(0), Some((0.5, 0.001)));
+        assert_eq!(header.time_base(1), None);
+        assert_eq!(header.time_base(2), None);
+    }
+
+    #[test]
+    fn enum_token_errors() {
+        let text = FILE.replace("Decimal_Separator\t.", "Decimal_Separator\t;");
+        let error = from_str(&text).unwrap_err();
+        assert_eq!(error.line(), Some(5));
+        assert!(error
+            .iter()
+            .any(|e| e.to_string() == "\";\" was found instead of \".\" or \",\""));
+
+        let text = FILE.replace("X_Columns\tOne", "X_Columns\tSome");
+        let error = from_str(&text).unwrap_err();
+        assert_eq!(error.line(), Some(7));
+        assert!(error.iter().any(|e| e.to_string().contains("\"No\" or \"One\" or \"Multi\"")));
+    }
+
+    #[test]
+    fn skip_default_fields() {
+        let file = from_str(FILE).unwrap();
+        let full = to_string(&file).unwrap();
+        let options = WriteOptions {
+            skip_defaults: true,
+            ..WriteOptions::default()
+        };
+        let minimal = to_string_with_options(&file, &options).unwrap();
+        assert!(minimal.len() < full.len());
+        for key in &["Decimal_Separator", "Multi_Headings", "X_Columns", "Description", "Notes"] {
+            assert!(full.contains(key));
+            assert!(!minimal.contains(key), "{} was written", key);
+        }
+        assert!(minimal.contains("Operator\tOperator\n"));
+        assert_eq!(from_str(&minimal).unwrap(), file);
+    }
+
+    #[test]
+    fn large_sample_counts() {
+        let text = FILE.replace("Samples\t3\t3\t", "Samples\t5000000000\t3\t");
+        let samples = from_str(&text).map(|file| file.measurements[0].header.samples[0] as u64);
+        if cfg!(target_pointer_width = "64") {
+            assert_eq!(samples.unwrap(), 5_000_000_000);
+        } else {
+            assert!(samples.is_err());
+        }
+
+        // A 32-bit usize
+        assert_eq!(lvm::narrow_count::<u32>(3).unwrap(), 3);
+        let error = lvm::narrow_count::<u32>(5_000_000_000).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The count 5000000000 does not fit in a u32 on this platform"
+        );
+    }
+
+    #[test]
+    fn header_transforms() {
+        let mut options = ReadOptions::default();
+        options
+            .transforms
+            .insert("Operator", |value| value.to_uppercase())
+            .insert("Samples", |value| value.replace("3", "4"));
+        let file = from_str_with_options(FILE, &options).unwrap();
+        assert_eq!(file.header.operator.unwrap().as_str(), "OPERATOR");
+        assert_eq!(file.measurements[0].header.samples, vec![4, 4]);
+        assert_eq!(file.measurements[0].data.len(), 3);
+    }
+
+    #[test]
+    fn combined_date_time() {
+        let text = FILE
+            .replace(
+                "Date\t2018/02/03\nTime\t12:34:56.789\n",
+                "Date_Time\t2018/02/03 12:34:56.789\n",
+            )
+            .replace(
+                "Date\t2018/02/03\t2018/02/03\t\nTime\t12:34:56.789\t12:34:56.789\t\n",
+                "Date_Time\t2018/02/03T12:34:56.789\t2018/02/04 01:02:03\t\n",
+            );
+        assert_eq!(text.matches("Date_Time").count(), 2);
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.header.date.to_string(), "2018/02/03");
+        assert_eq!(file.header.time.to_string(), "12:34:56.789");
+        let header = &file.measurements[0].header;
+        assert_eq!(header.date[1].to_string(), "2018/02/04");
+        assert_eq!(header.time[1].to_string(), "01:02:03");
+        assert_eq!(file.measurements[0].data.len(), 3);
+
+        let error = from_str(&text.replace("2018/02/04 01:02:03", "2018/02/04")).unwrap_err();
+        assert_eq!(error.line(), Some(15));
+    }
+
+    #[test]
+    fn summary() {
+        let packet = &FILE[FILE.find("Channels").unwrap()..];
+        let text = format!("{}\n{}", FILE, packet);
+        let summary = from_str(&text).unwrap().summary();
+        assert_eq!(
+            summary,
+            "Date: 2018/02/03 12:34:56.789\n\
+             Operator: Operator\n\
+             Measurements: 2\n  \
+             1: 2 channels, 6 samples\n  \
+             2: 2 channels, 6 samples\n\
+             Total samples: 12"
+        );
+    }
+
+    #[test]
+    fn totals() {
+        let packet = &FILE[FILE.find("Channels").unwrap()..];
+        let text = format!("{}\n{}", FILE, packet);
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.total_samples(), 12);
+        // Each row holds the x-value and one value for each of the two channels
+        assert_eq!(file.total_data_points(), 18);
+    }
+
+    #[test]
+    fn missing_versions() {
+        let text = FILE.replace("Writer_Version\t2\n", "");
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.header.writer_version.to_string(), "1.0");
+        assert_eq!(file.header.reader_version.to_string(), "2.0");
+
+        let text = text.replace("Reader_Version\t2\n", "");
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.header.reader_version.to_string(), "1.0");
+        assert!(file.header.is_compatible());
+    }
+
+    #[test]
+    fn comments_between_packets() {
+        let packet = &FILE[FILE.find("Channels").unwrap()..];
+        let text = format!("{}\n\trecalibrated\t\n\n\tsecond run\n{}", FILE, packet);
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.measurements.len(), 2);
+        assert_eq!(
+            file.comments,
+            vec![(27, "recalibrated".to_string()), (29, "second run".to_string())]
+        );
+        assert!(from_str(FILE).unwrap().comments.is_empty());
+    }
+
+    #[test]
+    fn packet_time_pref() {
+        let packet = &FILE[FILE.find("Channels").unwrap()..];
+        let absolute = packet.replace("X0\t", "Time_Pref\tAbsolute\nX0\t");
+        let text = format!("{}\n{}", FILE, absolute);
+        let file = from_str(&text).unwrap();
+        let prefs: Vec<_> = file.measurements
+            .iter()
+            .map(|measurement| measurement.time_pref(&file.header))
+            .collect();
+        assert_eq!(prefs, vec![&lvm::TimePref::Relative, &lvm::TimePref::Absolute]);
+        assert_eq!(from_str(&to_string(&file).unwrap()).unwrap(), file);
+    }
+
+    #[test]
+    fn custom_line_source() {
+        /// Lines handed out one at a time, without their terminators
+        struct Lines(std::vec::IntoIter<&'static str>);
+
+        impl ::ReadLine for Lines {
+            fn read_line(&mut self, o_line: &mut String) -> Result<usize> {
+                Ok(self.0.next().map_or(0, |line| {
+                    o_line.push_str(line);
+                    o_line.push('\n');
+                    line.len() + 1
+                }))
+            }
+        }
+
+        let lines = Lines(FILE.lines().collect::<Vec<_>>().into_iter());
+        let mut deserializer = ::Deserializer::from_reader(lines, &ReadOptions::default()).unwrap();
+        assert_eq!(deserializer.deserialize_file().unwrap(), from_str(FILE).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn file_entry_point() {
+        let mut reader =
+            Deserializer::from_buf_reader(FILE.as_bytes(), &ReadOptions::default()).unwrap();
+        assert_eq!(reader.deserialize_file().unwrap(), from_str(FILE).unwrap());
+
+        let text = FILE.replace("Channels\t2", "Channels\tx");
+        assert!(deserializer(&text).deserialize_file().is_err());
+    }
+
+    #[test]
+    fn column_major() {
+        let headings = FILE.find("X_Value").unwrap();
+        let body = "0.000\n0.001\n0.002\n1.0\n1.1\n1.2\n2.0\n2.1\n2.2\n";
+        let data = headings + FILE[headings..].find('\n').unwrap() + 1;
+        let text = format!("{}{}", &FILE[..data], body);
+        let options = ReadOptions {
+            column_major: true,
+            ..ReadOptions::default()
+        };
+        let file = from_str_with_options(&text, &options).unwrap();
+        let twin = from_str(FILE).unwrap();
+        assert_eq!(file.measurements[0].column(1), Some(vec![2.0, 2.1, 2.2]));
+        assert_eq!(file, twin);
+
+        let truncated = format!("{}{}", &text[..text.len() - 4], "\n");
+        let error = from_str_with_options(&truncated, &options).unwrap_err();
+        assert_eq!(error.line(), Some(31));
+    }
+
+    #[test]
+    fn boxed_errors() {
+        use SyncError;
+
+        fn parse(i_text: &str) -> std::result::Result<lvm::File, Box<dyn std::error::Error>> {
+            Ok(from_str(i_text)?)
+        }
+
+        fn parse_sync(
+            i_text: &str,
+        ) -> std::result::Result<lvm::File, Box<dyn std::error::Error + Send + Sync>> {
+            Ok(from_str(i_text).map_err(SyncError::from)?)
+        }
+
+        assert!(parse(FILE).is_ok());
+        assert!(parse_sync(FILE).is_ok());
+
+        let text = FILE.replace("Channels\t2", "Channels\tx");
+        let error = parse_sync(&text).unwrap_err();
+        assert_eq!(error.to_string(), "Error parsing line 14");
+        let mut causes = 0;
+        let mut source = error.source();
+        while let Some(cause) = source {
+            causes += 1;
+            source = cause.source();
+        }
+        assert!(causes >= 2);
+
+        let error = SyncError::from(from_str(&text).unwrap_err());
+        assert_eq!(error.line(), Some(14));
+        assert_eq!(error.column(), Some(11));
+    }
+
+    #[test]
+    fn sample_precision() {
+        let file = from_str(FILE).unwrap();
+        let lines = FILE.lines().skip(22).collect::<Vec<_>>();
+        let data = &file.measurements[0].data;
+        assert_eq!(data.len(), lines.len());
+        for ((values, _), line) in data.iter().zip(lines) {
+            let values = values.iter().cloned().map(lvm::widen).collect::<Vec<_>>();
+            let expected = line.split('\t').map(|value| value.parse().unwrap()).collect::<Vec<_>>();
+            assert_close(&values, &expected);
+        }
+    }
+
+    #[test]
+    fn missing_optional_fields() {
+        let file = from_str(FILE).unwrap();
+        assert_eq!(file.header.description, None);
+        let text = to_string(&file).unwrap();
+        assert!(text.contains("\nDescription\t\n"));
+        let reread = from_str(&text).unwrap();
+        assert_eq!(reread.header.description, None);
+        assert_eq!(reread, file);
+
+        let text = FILE.replace(
+            "Operator\tOperator\n",
+            "Operator\tOperator\nDescription\t\t\n",
+        );
+        let text = text.replace("X0\t", "Y_Unit_Label\t\t\t\nY0\t\t\t\nX0\t");
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.header.description, None);
+        assert_eq!(file.measurements[0].header.y_unit_label, None);
+        assert_eq!(file.measurements[0].header.y0, None);
+    }
+
+    #[test]
+    fn channel_names() {
+        let packet = &FILE[FILE.find("Channels").unwrap()..];
+        let second = packet.replace("\tUntitled\tUntitled 1\t", "\tVoltage\tUntitled\t");
+        let text = format!("{}\n{}", FILE, second);
+        let file = from_str(&text).unwrap();
+        let names = file.channel_names();
+        let names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+        assert_eq!(names, vec!["Untitled", "Untitled 1", "Voltage"]);
+    }
+
+    #[test]
+    fn channel_name_keys() {
+        let file = from_str(FILE).unwrap();
+        let measurement = &file.measurements[0];
+        let columns: std::collections::HashMap<lvm::ChannelName, Vec<lvm::Sample>> = file
+            .channel_names()
+            .into_iter()
+            .enumerate()
+            .map(|(index, name)| (name, measurement.column(index).unwrap()))
+            .collect();
+        let name = lvm::ChannelName::from("Untitled 1".to_string());
+        assert_eq!(columns[&name], vec![2.0, 2.1, 2.2]);
+        assert_eq!(columns.len(), 2);
+    }
+
+    #[test]
+    fn channel_index() {
+        let text = FILE.replace("Channels\t2\t\n", "Channels\t2\tLeft\tRight\t\n");
+        let file = from_str(&text).unwrap();
+        let header = &file.measurements[0].header;
+        assert_eq!(header.channel_index("Left"), Some(0));
+        assert_eq!(header.channel_index("Right"), Some(1));
+        assert_eq!(header.channel_index("right"), None);
+        assert_eq!(header.channel_index("Middle"), None);
+        assert_eq!(file.measurements[0].column_by_name("Right").unwrap(), vec![2.0, 2.1, 2.2]);
+        assert!(to_string(&file).unwrap().contains("\nChannels\t2\tLeft\tRight\t\n"));
+    }
+
+    #[test]
+    fn tuple_separators() {
+        // The channel names follow the count, separated from it like any other value
+        let file = from_str(FILE).unwrap();
+        assert_eq!(file.measurements[0].header.channels, (2, vec![]));
+        let text = FILE.replace("Channels\t2\t\n", "Channels\t2\tLeft\tRight\t\n");
+        let file = from_str(&text).unwrap();
+        let names: Vec<&str> =
+            file.measurements[0].header.channels.1.iter().map(|name| name.as_str()).collect();
+        assert_eq!(names, vec!["Left", "Right"]);
+
+        let written = to_string(&file).unwrap();
+        assert!(written.contains("\nChannels\t2\tLeft\tRight\t\n"));
+        assert_eq!(from_str(&written).unwrap(), file);
+    }
+
+    #[test]
+    fn magic_separator_mismatch() {
+        let file = from_str(FILE).unwrap();
+        assert_eq!(file.header.separator(), lvm::Separator::Tab);
+        assert_eq!(file.header.magic_separator(), lvm::Separator::Tab);
+
+        let end = FILE.find("***End_of_Header***").unwrap();
+        let data = end + FILE[end..].find('\n').unwrap() + 1;
+        let text = format!(
+            "{}{}",
+            FILE[..data].replace("Separator\tTab", "Separator\tComma"),
+            FILE[data..].replace('\t', ",")
+        );
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.header.magic_separator(), lvm::Separator::Tab);
+        assert_eq!(file.header.separator(), lvm::Separator::Comma);
+        assert_eq!(file.measurements[0].data[2].0, vec![0.002, 1.2, 2.2]);
+
+        let text = FILE.replace('\t', ",").replace("Separator,Tab\n", "");
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.header.magic_separator(), lvm::Separator::Comma);
+        assert_eq!(file.header.separator(), lvm::Separator::Comma);
+        let written = to_string(&file).unwrap();
+        assert!(written.starts_with("LabVIEW Measurement,\n"));
+        assert_eq!(from_str(&written).unwrap().measurements, file.measurements);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn malformed_input_never_panics() {
+        let parse = |input: &[u8], column_major: bool| {
+            let result = std::panic::catch_unwind(|| {
+                let _ = from_reader(input);
+                let options = ReadOptions {
+                    lenient: true,
+                    column_major,
+                    ..ReadOptions::default()
+                };
+                let _ = from_reader_with_options(input, &options);
+            });
+            assert!(result.is_ok(), "panicked on {:?}", String::from_utf8_lossy(input));
+        };
+
+        let cases = [
+            FILE.replace("X_Columns\tOne", "X_Columns\tMulti")
+                .replace("\tUntitled\t", "\tUntitled\tX_Value\t"),
+            FILE.replace("Channels\t2", "Channels\t99999999999"),
+            FILE.replace("Samples\t3\t3", "Samples\t99999999999"),
+            FILE.replace("Separator\tTab\n", "Separator\n"),
+            FILE.replace("Time\t12:34:56.789\n", "Date_Time\n"),
+            FILE.replace("\t\nChannels", "\nChannels"),
+        ];
+        for case in &cases {
+            parse(case.as_bytes(), false);
+            parse(case.as_bytes(), true);
+        }
+
+        // A xorshift generator keeps the inputs reproducible without extra dependencies
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+        let alphabet = b"\t\n\r ,.;:-+0123456789eE\"#*_YesNoTabComma\xff";
+        for round in 0..2000 {
+            let mut input = if round % 8 == 0 {
+                (0..next(64)).map(|_| next(256) as u8).collect()
+            } else {
+                FILE.as_bytes().to_vec()
+            };
+            for _ in 0..1 + next(4) {
+                if input.is_empty() {
+                    break;
+                }
+                let at = next(input.len());
+                match next(4) {
+                    0 => input[at] = alphabet[next(alphabet.len())],
+                    1 => {
+                        input.remove(at);
+                    }
+                    2 => input.insert(at, alphabet[next(alphabet.len())]),
+                    _ => input.truncate(at),
+                }
+            }
+            parse(&input, round % 2 == 0);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn max_line_length() {
+        let longest = FILE.lines().map(str::len).max().unwrap();
+        let options = ReadOptions {
+            max_line_length: Some(longest),
+            ..ReadOptions::default()
+        };
+        assert!(from_reader_with_options(FILE.as_bytes(), &options).is_ok());
+        assert!(from_str_with_options(FILE, &options).is_ok());
+
+        let long = format!("Operator\t{}", "x".repeat(1 << 20));
+        let text = FILE.replace("Operator\tOperator", &long);
+        for error in [
+            from_reader_with_options(text.as_bytes(), &options).unwrap_err(),
+            from_str_with_options(&text, &options).unwrap_err(),
+        ] {
+            let cause = error.iter().last().unwrap().to_string();
+            assert_eq!(cause, format!("A line is longer than the maximum of {} bytes", longest));
+        }
+    }
+
+    #[test]
+    fn second_less_times() {
+        let text = FILE.replace("12:34:56.789", "12:34");
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.header.time.to_string(), "12:34:00");
+        assert_eq!(file.measurements[0].header.time[1].to_string(), "12:34:00");
+
+        let text = FILE.replace("12:34:56.789", "12:34:56");
+        assert_eq!(from_str(&text).unwrap().header.time.to_string(), "12:34:56");
+        assert!(from_str(&FILE.replace("12:34:56.789", "12")).is_err());
+    }
+
+    #[test]
+    fn twelve_hour_times() {
+        let text = FILE.replace("12:34:56.789", "02:30:00 PM");
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.header.time.to_string(), "14:30:00");
+        assert_eq!(file.measurements[0].header.time[1].to_string(), "14:30:00");
+
+        for &(time, expected) in &[
+            ("12:05:01.5 AM", "00:05:01.500"),
+            ("12:05 pm", "12:05:00"),
+            ("11:59:59 PM", "23:59:59"),
+        ] {
+            let file = from_str(&FILE.replace("12:34:56.789", time)).unwrap();
+            assert_eq!(file.header.time.to_string(), expected);
+        }
+        assert!(from_str(&FILE.replace("12:34:56.789", "13:00:00 PM")).is_err());
+    }
+
+    #[test]
+    fn date_formats() {
+        for date in &["2018/2/3", "2018-02-03", "2018-2-3", "2018/02/3"] {
+            let file = from_str(&FILE.replace("2018/02/03", date)).unwrap();
+            assert_eq!(file.header.date.to_string(), "2018/02/03");
+            assert_eq!(file.measurements[0].header.date[1].to_string(), "2018/02/03");
+        }
+        assert!(from_str(&FILE.replace("2018/02/03", "2018.02.03")).is_err());
+    }
+
+    #[test]
+    fn validate_all() {
+        let file = from_str(FILE).unwrap();
+        assert!(file.validate_all().is_ok());
+
+        let causes = |file: &lvm::File| -> Vec<String> {
+            let error = file.validate_all().unwrap_err();
+            error.iter().map(|cause| cause.to_string()).collect()
+        };
+
+        let mut file = from_str(FILE).unwrap();
+        file.measurements[0].data_headings.remove(2);
+        assert_eq!(
+            causes(&file),
+            vec!["Measurement 1 is inconsistent", "1 data headings were found for 2 channels"]
+        );
+
+        let mut file = from_str(FILE).unwrap();
+        file.measurements[0].header.x0.pop();
+        assert_eq!(causes(&file)[1], "X0 has 1 values for 2 channels");
+
+        let mut file = from_str(FILE).unwrap();
+        file.measurements[0].data[1].0.pop();
+        assert_eq!(causes(&file)[1], "Data row 2 has 2 values instead of 3");
+
+        let packet = &FILE[FILE.find("Channels").unwrap()..];
+        let text = format!("{}\n{}", FILE, packet.replace("Samples\t3\t3", "Samples\t3\t4"));
+        let file = from_str(&text).unwrap();
+        assert_eq!(
+            causes(&file),
+            vec!["Measurement 2 is inconsistent", "Channel 2 declares 4 samples but 3 were found"]
+        );
+    }
+
+    #[test]
+    fn negative_and_zero_delta_x() {
+        let text = FILE.replace("Delta_X\t0.001\t0.001", "Delta_X\t-0.5\t0");
+        let file = from_str(&text).unwrap();
+        assert!(file.validate_all().is_ok());
+        let measurement = &file.measurements[0];
+        assert_eq!(measurement.header.delta_x, vec![-0.5, 0.0]);
+        assert_eq!(measurement.header.sample_rate(0), Some(-2.0));
+        assert_eq!(measurement.header.sample_rate(1), None);
+
+        let x_values: Vec<f64> = measurement.waveform(0).unwrap().x_values().collect();
+        assert_eq!(x_values, vec![0.0, -0.5, -1.0]);
+        let x_values: Vec<f64> = measurement.waveform(1).unwrap().x_values().collect();
+        assert_eq!(x_values, vec![0.0, 0.0, 0.0]);
+        assert_eq!(from_str(&to_string(&file).unwrap()).unwrap(), file);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn independent_parses_equal() {
+        let first = from_str(FILE).unwrap();
+        let second = from_reader(FILE.as_bytes()).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first.header, second.header);
+        assert_eq!(first.measurements[0], second.measurements[0]);
+        assert_eq!(first.measurements[0].header, second.measurements[0].header);
+
+        let changed = from_str(&FILE.replace("2.1\n", "2.15\n")).unwrap();
+        assert_ne!(first, changed);
+        assert_eq!(first.header, changed.header);
+        assert_ne!(first.measurements[0], changed.measurements[0]);
+    }
+
+    #[test]
+    fn diadem_headers() {
+        let text = FILE.replace(
+            "X_Dimension\t",
+            "UUT_SN\tSN-1\nUUT_MN\tMN-2\nChannel_Group\tGroup 1\t\nX_Dimension\t",
+        );
+        let file = from_str(&text).unwrap();
+        let header = &file.measurements[0].header;
+        assert_eq!(header.uut_sn.as_ref().unwrap().as_str(), "SN-1");
+        assert_eq!(header.uut_mn.as_ref().unwrap().as_str(), "MN-2");
+
+        let written = to_string(&file).unwrap();
+        assert!(written.contains("\nUUT_S/N\tSN-1"));
+        assert_eq!(from_str(&written).unwrap(), file);
+    }
+
+    #[test]
+    fn multi_headings() {
+        let packet = &FILE[FILE.find("Channels").unwrap()..];
+        let other = packet
+            .replace("Samples\t3\t3", "Samples\t2\t2")
+            .replace("0.002\t1.2\t2.2\n", "");
+        let text = format!("{}\n{}\n{}", FILE, packet, other);
+
+        let file = from_str(&text.replace("Multi_Headings\tNo", "Multi_Headings\tYes")).unwrap();
+        assert!(file.header.multi_headings());
+        let written = to_string(&file).unwrap();
+        assert_eq!(written.matches("\nChannels\t").count(), 3);
+        assert_eq!(from_str(&written).unwrap(), file);
+
+        let file = from_str(&text).unwrap();
+        assert!(!file.header.multi_headings());
+        assert_eq!(file.measurements.len(), 3);
+        let written = to_string(&file).unwrap();
+        assert_eq!(written.matches("\nChannels\t").count(), 2);
+        let reread = from_str(&written).unwrap();
+        assert_eq!(reread, file);
+        assert_eq!(reread.measurements[1].data, file.measurements[0].data);
+    }
+
+    #[test]
+    fn resample_channel() {
+        // The channels follow y = 1 + 100 x and y = 2 + 100 x
+        let file = from_str(FILE).unwrap();
+        let measurement = &file.measurements[0];
+        assert_close(&measurement.resample_channel(0, 0.0005), &[1.0, 1.05, 1.1, 1.15, 1.2]);
+        assert_close(&measurement.resample_channel(1, 0.0015), &[2.0, 2.15]);
+        assert_close(&measurement.resample_channel(1, 0.002), &[2.0, 2.2]);
+        assert_close(&measurement.resample_channel(0, 0.001), &[1.0, 1.1, 1.2]);
+        assert_close(&measurement.resample_channel(0, 1.0), &[1.0]);
+
+        assert!(measurement.resample_channel(0, 0.0).is_empty());
+        assert!(measurement.resample_channel(0, -0.001).is_empty());
+        assert!(measurement.resample_channel(2, 0.001).is_empty());
+    }
+
+    #[test]
+    fn notes_per_channel() {
+        let with_notes = |notes: &str| {
+            FILE.replace(
+                "Delta_X\t0.001\t0.001\t\n",
+                &format!("Delta_X\t0.001\t0.001\t\nNotes\t{}\n", notes),
+            )
+        };
+
+        let file = from_str(&with_notes("left probe\tright probe\t")).unwrap();
+        let header = &file.measurements[0].header;
+        assert_eq!(
+            header.notes_per_channel,
+            vec![Some("left probe".to_string()), Some("right probe".to_string())]
+        );
+        assert_eq!(from_str(&to_string(&file).unwrap()).unwrap(), file);
+
+        let file = from_str(&with_notes("\tright probe\t")).unwrap();
+        let header = &file.measurements[0].header;
+        assert_eq!(header.notes_per_channel, vec![None, Some("right probe".to_string())]);
+
+        // A single note covers the whole packet
+        let file = from_str(&with_notes("both probes\t")).unwrap();
+        let header = &file.measurements[0].header;
+        assert!(header.notes.as_ref().unwrap().starts_with("both probes"));
+        assert!(header.notes_per_channel.is_empty());
+    }
+
+    #[test]
+    fn plain_rows_match_general_path() {
+        let text = FILE
+            .replace("0.001\t1.1\t2.1", "1E-3\t-1.1e0\t+2.1")
+            .replace("0.002\t1.2\t2.2", "0.002\tinf\t-0");
+        // Rows separated by commas do not take the tab-separated fast path
+        let general = text.replace('\t', ",").replace("Separator,Tab", "Separator,Comma");
+
+        let plain = from_str(&text).unwrap();
+        let general = from_str(&general).unwrap();
+        assert_eq!(plain.measurements, general.measurements);
+        assert_eq!(plain.measurements[0].data[2].0[1], lvm::Sample::INFINITY);
+
+        // A number past the values of a row is its comment, as it is on the general path
+        let commented = text.replace("\t+2.1\n", "\t+2.1\t42\n");
+        let plain = from_str(&commented).unwrap();
+        let general = commented.replace('\t', ",").replace("Separator,Tab", "Separator,Comma");
+        assert_eq!(plain.measurements, from_str(&general).unwrap().measurements);
+        assert_eq!(plain.measurements[0].data[1].1.as_deref(), Some("42"));
+        plain.validate_all().unwrap();
+        let plain = from_str(&text).unwrap();
+
+        // Rows the fast path does not take are still read or rejected as before
+        let options = ReadOptions {
+            lenient: true,
+            ..ReadOptions::default()
+        };
+        let padded = text.replace("\t-0\n", "\t-0\t\n");
+        assert_eq!(from_str_with_options(&padded, &options).unwrap(), plain);
+        assert!(from_str(&text.replace("\t-0\n", "\t\t-0\n")).is_err());
+    }
+
+    #[test]
+    fn multi_x_columns() {
+        let text = FILE
+            .replace("X_Columns\tOne", "X_Columns\tMulti")
+            .replace("X_Value\tUntitled\t", "X_Value\tUntitled\tX_Value\t")
+            .replace("0.000\t1.0\t2.0", "0.000\t1.0\t0.000\t2.0")
+            .replace("0.001\t1.1\t2.1", "0.001\t1.1\t0.001\t2.1")
+            .replace("0.002\t1.2\t2.2", "0.002\t1.2\t0.002\t2.2");
+        let file = from_str(&text).unwrap();
+        file.validate_all().unwrap();
+        let measurement = &file.measurements[0];
+        assert_close(&measurement.scaled_column(0).unwrap(), &[1.0, 1.1, 1.2]);
+        assert_close(&measurement.scaled_column(1).unwrap(), &[2.0, 2.1, 2.2]);
+        assert_eq!(measurement.channel(0).heading(), Some("Untitled"));
+        assert_eq!(measurement.channel(1).heading(), Some("Untitled 1"));
+        assert_eq!(measurement.column_by_name("Untitled 1"), measurement.column(1));
+        assert_eq!(measurement.column_by_name("X_Value"), None);
+
+        let written = to_string(&file).unwrap();
+        assert!(written.contains("\nX_Value\tUntitled\tX_Value\tUntitled 1\tComment\n"));
+        assert!(written.contains("\n0.001\t1.1\t0.001\t2.1\n"));
+        assert_eq!(from_str(&written).unwrap(), file);
+
+        let options = ReadOptions {
+            column_major: true,
+            ..ReadOptions::default()
+        };
+        let columns = text.replace(
+            "0.000\t1.0\t0.000\t2.0\n0.001\t1.1\t0.001\t2.1\n0.002\t1.2\t0.002\t2.2\n",
+            "0.000\n0.001\n0.002\n1.0\n1.1\n1.2\n0.000\n0.001\n0.002\n2.0\n2.1\n2.2\n",
+        );
+        let reread = from_str_with_options(&columns, &options).unwrap();
+        assert_eq!(reread.measurements, file.measurements);
+    }
+
+    #[test]
+    fn back_to_back_packets() {
+        let packet = &FILE[FILE.find("Channels").unwrap()..];
+        let second = packet.replace("Untitled 1", "Second");
+        let text = format!("{}\n{}", FILE, second);
+        for &x_columns in &[lvm::XColumns::No, lvm::XColumns::One, lvm::XColumns::Multi] {
+            let mut file = from_str(&text).unwrap();
+            file.header.x_columns = x_columns;
+            for measurement in &mut file.measurements {
+                if x_columns == lvm::XColumns::Multi {
+                    measurement.data_headings.insert(2, "X_Value".to_string());
+                }
+                for row in &mut measurement.data {
+                    row.0 = match x_columns {
+                        lvm::XColumns::No => row.0[1..].to_vec(),
+                        lvm::XColumns::One => row.0.clone(),
+                        lvm::XColumns::Multi => vec![row.0[0], row.0[1], row.0[0], row.0[2]],
+                    };
+                }
+            }
+            // Repeating the last header leaves the third packet without one of its own
+            let third = lvm::Measurement {
+                header: file.measurements[1].header.clone(),
+                data_headings: file.measurements[1].data_headings.clone(),
+                data: file.measurements[0].data.clone(),
+                raw_data: vec![],
+            };
+            file.measurements.push(third);
+            file.validate_all().unwrap();
+
+            let written = to_string(&file).unwrap();
+            assert_eq!(written.matches("Channels").count(), 2, "{:?}", x_columns);
+            let mut deserializer = Deserializer::from_reader(&written[..], &ReadOptions::default())
+                .unwrap();
+            let reread = deserialize_file(&mut deserializer, 0, |_| {}).unwrap();
+            assert_eq!(reread, file, "{:?}", x_columns);
+            assert!(deserializer.following(), "{:?}", x_columns);
+        }
+    }
+
+    #[test]
+    fn row_comments() {
+        let data = "\t1.0\t2.0\tfirst\n\
+                    \t1.1\t2.1\n\
+                    \t1.2\t2.2\tlast, with text\n";
+        let header = FILE[..FILE.find("0.000").unwrap()]
+            .replace("X_Columns\tOne", "X_Columns\tNo");
+        let file = from_str(&format!("{}{}", header, data)).unwrap();
+        let measurement = &file.measurements[0];
+        assert!(measurement.has_comment_column());
+        let comments: Vec<_> = measurement.data.iter().map(|row| row.1.as_deref()).collect();
+        assert_eq!(comments, vec![Some("first"), None, Some("last, with text")]);
+        assert_eq!(measurement.data[2].0, vec![1.2, 2.2]);
+        file.validate_all().unwrap();
+        assert_eq!(from_str(&to_string(&file).unwrap()).unwrap(), file);
+
+        let text = FILE.replace("0.001\t1.1\t2.1", "0.001\t1.1\t2.1\tspike");
+        let file = from_str(&text).unwrap();
+        assert_eq!(file.measurements[0].data[1].1.as_deref(), Some("spike"));
+        assert_eq!(to_string(&file).unwrap().matches("\tspike\n").count(), 1);
+    }
+
+    #[test]
+    fn start_time() {
+        let text = FILE
+            .replace("Date\t2018/02/03\t2018/02/03", "Date\t2018/02/03\t2018/02/02")
+            .replace("Time\t12:34:56.789\t12:34:56.789", "Time\t12:34:56.789\t23:59:59");
+        let mut file = from_str(&text).unwrap();
+        let start = chrono::NaiveDate::from_ymd_opt(2018, 2, 2)
+            .and_then(|date| date.and_hms_opt(23, 59, 59));
+        assert_eq!(file.measurements[0].start_time(), start);
+
+        let measurement = &mut file.measurements[0];
+        measurement.header.time.truncate(1);
+        let start = chrono::NaiveDate::from_ymd_opt(2018, 2, 3)
+            .and_then(|date| date.and_hms_milli_opt(12, 34, 56, 789));
+        assert_eq!(measurement.start_time(), start);
+        measurement.header.date.clear();
+        assert_eq!(measurement.start_time(), None);
+    }
+
+    #[test]
+    fn clone_file() {
+        let file = from_str(FILE).unwrap();
+        let mut copy = file.clone();
+        assert_eq!(copy, file);
+
+        copy.header.x_columns = lvm::XColumns::Multi;
+        copy.measurements[0].header.channels.0 = 1;
+        copy.measurements[0].data[0].0[1] = 5.0;
+        copy.measurements[0].data[0].1 = Some("edited".to_string());
+        copy.measurements.push(copy.measurements[0].clone());
+        assert_eq!(file.header.x_columns, lvm::XColumns::One);
+        assert_eq!(file.measurements.len(), 1);
+        assert_eq!(file.measurements[0].header.channels.0, 2);
+        assert_eq!(file.measurements[0].data[0], (vec![0.0, 1.0, 2.0], None));
+        assert_eq!(file, from_str(FILE).unwrap());
+    }
+
+    #[test]
+    fn channels_after_dependent_fields() {
+        let text = FILE.replace(
+            "Channels\t2\t\nSamples\t3\t3\t\n",
+            "Samples\t3\t3\t\nChannels\t2\t\n",
+        );
+        assert!(text.contains("\nSamples\t3\t3\t\nChannels\t2\t\n"));
+        assert_eq!(from_str(&text).unwrap(), from_str(FILE).unwrap());
+
+        // Channels may even close the header, and still bounds the separators ending it
+        let text = FILE
+            .replace("Channels\t2\t\n", "")
+            .replace("Delta_X\t0.001\t0.001\t\n", "Delta_X\t0.001\t0.001\t\nChannels\t2\t\n");
+        let file = from_str(&text).unwrap();
+        assert_eq!(file, from_str(FILE).unwrap());
+        file.validate_all().unwrap();
+        assert!(to_string(&file).unwrap().contains("***\t\n\t\nChannels\t2\t\n"));
+
+        let text = text.replace("Samples\t3\t3\t\n", "Samples\t3\t3\t3\t\n");
+        let error = from_str(&text).unwrap().validate_all().unwrap_err();
+        let mismatch = error.find_innermost(|kind| match *kind {
+            ErrorKind::ChannelValueCountMismatch(key, found, channels) => {
+                Some((key, found, channels))
+            }
+            _ => None,
+        });
+        assert_eq!(mismatch, Some(("Samples", 3, 2)));
+    }
+
+    #[test]
+    fn append_row() {
+        let mut measurement = from_str(FILE).unwrap().measurements.remove(0);
+        measurement.append_row((vec![0.003, 1.3, 2.3], Some("note".to_string()))).unwrap();
+        assert_eq!(measurement.data.len(), 4);
+        measurement.header.samples = vec![4, 4];
+        measurement.validate(lvm::XColumns::One).unwrap();
+
+        let error = measurement.append_row((vec![0.004, 1.4], None)).unwrap_err();
+        match *error.kind() {
+            ErrorKind::RowWidthMismatch(5, 2, 3) => {}
+            ref kind => panic!("{:?}", kind),
+        }
+        assert_eq!(error.to_string(), "Data row 5 has 2 values instead of 3");
+        assert_eq!(measurement.data.len(), 4);
+
+        // The first row settles the layout, here of values without x-values
+        measurement.data.clear();
+        measurement.append_row((vec![1.0, 2.0], None)).unwrap();
+        assert!(measurement.append_row((vec![0.001, 1.1, 2.1], None)).is_err());
+        measurement.data.clear();
+        measurement.append_row((vec![0.0, 1.0, 0.0, 2.0], None)).unwrap();
+        measurement.data.clear();
+        match *measurement.append_row((vec![1.0], None)).unwrap_err().kind() {
+            ErrorKind::RowWidthMismatch(1, 1, 3) => {}
+            ref kind => panic!("{:?}", kind),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn forced_separator() {
+        let comma = FILE.replace('\t', ",").replace("Separator,Tab", "Separator,Comma");
+        let expected = from_str(&comma).unwrap();
+        assert_eq!(expected.header.separator(), lvm::Separator::Comma);
+
+        // The magic line ends with a tab and the header declares tabs, but commas split the rest
+        let mixed = comma
+            .replacen("LabVIEW Measurement,", "LabVIEW Measurement\t", 1)
+            .replace("Separator,Comma", "Separator,Tab");
+        assert!(from_str(&mixed).is_err());
+        let file = from_reader_with_separator(mixed.as_bytes(), lvm::Separator::Comma).unwrap();
+        assert_eq!(file.measurements, expected.measurements);
+        assert_eq!(file.header.separator(), lvm::Separator::Comma);
+        assert_eq!(file.header.magic_separator(), lvm::Separator::Comma);
+
+        // Nor does a magic line without a separator say anything
+        let bare = comma.replacen("LabVIEW Measurement,", "LabVIEW Measurement", 1);
+        assert!(from_str(&bare).is_err());
+        let file = from_reader_with_separator(bare.as_bytes(), lvm::Separator::Comma).unwrap();
+        assert_eq!(file.measurements, expected.measurements);
+
+        let other = comma.replacen("LabVIEW Measurement", "LabVIEW Measurements", 1);
+        assert!(from_reader_with_separator(other.as_bytes(), lvm::Separator::Comma).is_err());
+    }
+
+    #[test]
+    fn date_time_errors() {
+        let cases = [
+            (
+                FILE.replacen("Date\t2018/02/03\n", "Date\t2018/02/30\n", 1),
+                10,
+                "2018/02/30".parse::<lvm::Date>().unwrap_err(),
+            ),
+            (
+                FILE.replace("Time\t12:34:56.789\t12:34:56.789", "Time\t12:34:56.789\t12:xx"),
+                17,
+                "12:xx".parse::<lvm::Time>().unwrap_err(),
+            ),
+        ];
+        for &(ref text, line, parse_error) in &cases {
+            let error = from_str(text).unwrap_err();
+            assert_eq!(error.line(), Some(line));
+            let message = error.find_innermost(|kind| match *kind {
+                ErrorKind::Deserialize(ref message) => Some(message.clone()),
+                _ => None,
+            });
+            assert_eq!(message, Some(parse_error.to_string()));
+            match *Error::from(parse_error).kind() {
+                ErrorKind::ParseDateTimeError(_) => {}
+                ref kind => panic!("{:?}", kind),
+            }
+        }
+
+        // Other formats still go through the visitor
+        let file = from_str(FILE).unwrap();
+        let date: lvm::Date = serde::de::Deserialize::deserialize(
+            serde::de::value::StrDeserializer::<Error>::new("2018-02-03"),
+        ).unwrap();
+        assert_eq!(date, file.header.date);
+    }
+
+    #[test]
+    fn version_errors() {
+        let text = FILE.replace("Reader_Version\t2\n", "Reader_Version\t2.x\n");
+        let error = from_str(&text).unwrap_err();
+        assert_eq!(error.line(), Some(3));
+        let parse_error = "2.x".parse::<lvm::Version>().unwrap_err();
+        let message = error.find_innermost(|kind| match *kind {
+            ErrorKind::Deserialize(ref message) => Some(message.clone()),
+            _ => None,
+        });
+        assert_eq!(message, Some(parse_error.to_string()));
+        match *Error::from(parse_error).kind() {
+            ErrorKind::ParseVersionError(_) => {}
+            ref kind => panic!("{:?}", kind),
+        }
+    }
+
+    #[test]
+    fn ragged_rows() {
+        let text = FILE
+            .replace("Samples\t3\t3", "Samples\t3\t2")
+            .replace("0.002\t1.2\t2.2\n", "0.002\t1.2\n");
+        let file = from_str(&text).unwrap();
+        file.validate_all().unwrap();
+        let measurement = &file.measurements[0];
+        assert!(measurement.data.iter().all(|(values, _)| values.len() == 3));
+        assert_eq!(measurement.data[2].0[..2], [0.002, 1.2]);
+        assert!(measurement.data[2].0[2].is_nan());
+        assert_eq!(measurement.column(0).unwrap(), vec![1.0, 1.1, 1.2]);
+        // The NaN filling the short row keeps the file from comparing equal to itself
+        assert_ne!(file, file.clone());
+
+        let options = ReadOptions {
+            column_major: true,
+            ..ReadOptions::default()
+        };
+        let columns = text.replace(
+            "0.000\t1.0\t2.0\n0.001\t1.1\t2.1\n0.002\t1.2\n",
+            "0.000\n0.001\n0.002\n1.0\n1.1\n1.2\n2.0\n2.1\n",
+        );
+        let reread = from_str_with_options(&columns, &options).unwrap();
+        assert_eq!(reread.measurements[0].data[..2], measurement.data[..2]);
+        assert!(reread.measurements[0].data[2].0[2].is_nan());
+
+        // The longest channel must still fill every row
+        let text = text.replace("Samples\t3\t2", "Samples\t2\t2");
+        assert!(from_str(&text).unwrap().validate_all().is_err());
+    }
+
+    #[test]
+    fn unit_labels() {
+        let file = from_str(FILE).unwrap();
+        assert_eq!(file.measurements[0].x_unit(0), None);
+        assert_eq!(file.measurements[0].y_unit(0), None);
+
+        let text = FILE.replace(
+            "X0\t0.0\t0.0\t\n",
+            "X0\t0.0\t0.0\t\nX_Unit_Label\tSeconds\t\t\nY_Unit_Label\tVolts\t\n",
+        );
+        let file = from_str(&text).unwrap();
+        let measurement = &file.measurements[0];
+        assert_eq!(measurement.x_unit(0), Some("Seconds"));
+        assert_eq!(measurement.x_unit(1), None);
+        assert_eq!(measurement.y_unit(0), Some("Volts"));
+        assert_eq!(measurement.y_unit(1), None);
+        assert_eq!(measurement.y_unit(2), None);
+    }
+
+    #[test]
+    fn partial_unit_labels() {
+        let text = FILE.replace(
+            "X0\t0.0\t0.0\t\n",
+            "X0\t0.0\t0.0\t\nX_Unit_Label\ts\t\nY_Unit_Label\tV\t\n",
+        );
+        let file = from_str(&text).unwrap();
+        let header = &file.measurements[0].header;
+        assert_eq!(header.y_unit_label.as_ref().map(Vec::len), Some(1));
+        assert_eq!(header.x_unit_label(0), Some(&lvm::Unit::Other("s".to_string())));
+        assert_eq!(header.x_unit_label(1), None);
+        assert_eq!(header.y_unit_label(0), Some(&lvm::Unit::Other("V".to_string())));
+        assert_eq!(header.y_unit_label(1), None);
+        assert_eq!(header.y_unit_label(usize::MAX), None);
+        assert_eq!(file.measurements[0].y_unit(1), None);
+        assert_eq!(from_str(FILE).unwrap().measurements[0].header.y_unit_label(0), None);
+        file.validate_all().unwrap();
+
+        let text = text.replace("Y_Unit_Label\tV\t\n", "Y_Unit_Label\tV\tV\tV\t\n");
+        let error = from_str(&text).unwrap().validate_all().unwrap_err();
+        let mismatch = error.find_innermost(|kind| match *kind {
+            ErrorKind::ChannelValueCountMismatch(key, found, channels) => {
+                Some((key, found, channels))
+            }
+            _ => None,
+        });
+        assert_eq!(mismatch, Some(("Y_Unit_Label", 3, 2)));
+    }
+
+    #[test]
+    fn unicode_unit_labels() {
+        let text = FILE.replace(
+            "X0\t0.0\t0.0\t\n",
+            "X0\t0.0\t0.0\t\nX_Unit_Label\tm/s²\tµs\t\nY_Unit_Label\t°C\tµV\t\n",
+        );
+        let file = from_str(&text).unwrap();
+        let measurement = &file.measurements[0];
+        assert_eq!(measurement.x_unit(0), Some("m/s²"));
+        assert_eq!(measurement.x_unit(1), Some("µs"));
+        assert_eq!(measurement.y_unit(0), Some("°C"));
+        assert_eq!(measurement.y_unit(1), Some("µV"));
+        // The values after the labels are still found where they start
+        assert_eq!(measurement.header.delta_x, vec![0.001, 0.001]);
+        assert_eq!(from_str(&to_string(&file).unwrap()).unwrap(), file);
+
+        let comma = text.replace('\t', ",").replace("Separator,Tab", "Separator,Comma");
+        assert_eq!(from_str(&comma).unwrap().measurements, file.measurements);
+
+        // Labels embedded in unit types are cut out around the multi-byte characters
+        let text = FILE.replace(
+            "X_Dimension\tTime\tTime",
+            "X_Dimension\tTemperature (°C)\tAcceleration (m/s²)",
+        );
+        let header = from_str(&text).unwrap().measurements.remove(0).header;
+        let labels: Vec<String> =
+            header.x_unit_label.unwrap().iter().map(ToString::to_string).collect();
+        assert_eq!(labels, vec!["°C", "m/s²"]);
+    }
+
+    #[test]
+    fn csv_export() {
+        let packet = &FILE[FILE.find("Channels").unwrap()..];
+        let second = packet.replace("\tUntitled\tUntitled 1\t", "\tVoltage, A\tCurrent \"B\"\t");
+        let text = format!("{}\n{}", FILE, second);
+        let mut file = from_str(&text).unwrap();
+        file.measurements[1].data[2].1 = Some("last".to_string());
+        assert_eq!(
+            file.to_csv_string().unwrap(),
+            "X_Value,Untitled,Untitled 1,Comment\n\
+             0,1,2,\n\
+             0.001,1.1,2.1,\n\
+             0.002,1.2,2.2,\n\
+             \n\
+             X_Value,\"Voltage, A\",\"Current \"\"B\"\"\",Comment\n\
+             0,1,2,\n\
+             0.001,1.1,2.1,\n\
+             0.002,1.2,2.2,last\n"
+        );
+
+        file.header.x_columns = lvm::XColumns::No;
+        for measurement in &mut file.measurements {
+            measurement.data_headings.pop();
+            for row in &mut measurement.data {
+                row.0.remove(0);
+            }
+        }
+        assert!(file.measurements[1].to_csv_string(lvm::XColumns::No).starts_with(
+            "\"Voltage, A\",\"Current \"\"B\"\"\"\n1,2\n"
+        ));
+        file.measurements[1].data_headings.pop();
+        let error = file.to_csv_string().unwrap_err();
+        match *error.kind() {
+            ErrorKind::InvalidMeasurement(2) => {}
+            ref kind => panic!("{:?}", kind),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn indexed_file() {
+        let packet = &FILE[FILE.find("Channels").unwrap()..];
+        let first = FILE.replace("0.001\t1.1\t2.1", "0.001\tbroken\t2.1");
+        let second = packet
+            .replace("Samples\t3\t3", "Samples\t2\t2")
+            .replace("0.002\t1.2\t2.2\n", "");
+        let path = temp_path("indexed_file");
+        std::fs::write(&path, format!("{}\n{}\n", first, second)).unwrap();
+
+        let indexed = lvm::File::open_indexed(&path).unwrap();
+        assert_eq!(indexed.len(), 2);
+        assert_eq!(indexed.header().x_columns, lvm::XColumns::One);
+        assert_eq!(indexed.measurement_header(1).samples, vec![2, 2]);
+        let measurement = indexed.load_measurement_data(1).unwrap();
+        assert_eq!(measurement.data.len(), 2);
+        let values = measurement.data[1].0.iter().cloned().map(lvm::widen).collect::<Vec<_>>();
+        assert_close(&values, &[0.001, 1.1, 2.1]);
+        assert_eq!(measurement.data_headings[2], "Untitled 1");
+        let error = indexed.load_measurement_data(0).unwrap_err();
+        assert_eq!(error.line(), Some(24));
+        assert_eq!(error.line(), from_str(&first).unwrap_err().line());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn dynamic_value() {
+        use value::{to_value, Value};
+
+        let file = from_str(FILE).unwrap();
+        let value = to_value(&file).unwrap();
+        let header = value.get("header").unwrap();
+        assert_eq!(header.get("Date"), Some(&Value::String("2018/02/03".to_string())));
+        assert_eq!(header.get("Separator"), Some(&Value::String("Tab".to_string())));
+        assert_eq!(header.get("Multi_Headings"), Some(&Value::Bool(false)));
+        assert_eq!(header.get("Description"), Some(&Value::Null));
+        assert_eq!(value.get("raw_header"), None);
+
+        let measurements = match value.get("measurements") {
+            Some(Value::Array(measurements)) => measurements,
+            v => panic!("unexpected {:?}", v),
+        };
+        assert_eq!(measurements.len(), 1);
+        let channels = measurements[0].get("header").unwrap().get("Channels").unwrap();
+        match *channels {
+            Value::Array(ref count_and_names) => assert_eq!(count_and_names[0], Value::Unsigned(2)),
+            ref v => panic!("unexpected {:?}", v),
+        }
+        let row = match measurements[0].get("data") {
+            Some(Value::Array(rows)) => rows[1].clone(),
+            v => panic!("unexpected {:?}", v),
+        };
+        let values = file.measurements[0].data[1].0.iter().map(|&v| Value::Float(lvm::widen(v)));
+        assert_eq!(row, Value::Array(vec![Value::Array(values.collect()), Value::Null]));
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn json_export() {
+        // Values exactly representable as `f32` keep the output the same for either `Sample`
+        let text = FILE
+            .replace("0.001\t1.1\t2.1\n", "0.5\t1.5\t2.5\n")
+            .replace("0.002\t1.2\t2.2\n", "1.0\t1.25\t2.25\n");
+        let mut file = from_str(&text).unwrap();
+        file.measurements[0].data[1].1 = Some("spike".to_string());
+        file.measurements[0].data[2].0[2] = lvm::Sample::NAN;
+        let expected = concat!(
+            r#"{"header":{"Date":"2018/02/03","Description":null,"Decimal_Separator":".","#,
+            r#""Multi_Headings":false,"Operator":"Operator","Project":null,"#,
+            r#""Reader_Version":"2.0","Separator":"Tab","Time":"12:34:56.789","#,
+            r#""Time_Pref":"Relative","Writer_Version":"2.0","X_Columns":"One"},"#,
+            r#""measurements":[{"header":{"Channels":[2,[]],"#,
+            r#""Date":["2018/02/03","2018/02/03"],"Delta_X":[0.001,0.001],"Interval":null,"#,
+            r#""Notes":null,"Samples":[3,3],"Test_Name":null,"Test_Number":null,"#,
+            r#""Test_Series":null,"Time":["12:34:56.789","12:34:56.789"],"Time_Pref":null,"#,
+            r#""UUT_M/N":null,"UUT_Name":null,"UUT_S/N":null,"X0":[0.0,0.0],"#,
+            r#""X_Dimension":["Time","Time"],"X_Unit_Label":null,"Y0":null,"#,
+            r#""Y_Dimension":["Electric_Potential","Electric_Potential"],"Y_Scale":null,"#,
+            r#""Y_Unit_Label":null},"#,
+            r#""data_headings":["X_Value","Untitled","Untitled 1","Comment"],"#,
+            r#""data":[[0.0,1.0,2.0],[0.5,1.5,2.5],[1.0,1.25,null]],"#,
+            r#""comments":[[1,"spike"]]}]}"#,
+        );
+        assert_eq!(file.to_json(), expected);
+    }
+
+    #[test]
+    fn raw_data() {
+        let data = "0.000\t1.10\t2.000\n\
+                    1E-3\t+1.1\t2.10\n\
+                    0.002\t1.20\t2.2\n";
+        let text = format!("{}{}", &FILE[..FILE.find("0.000").unwrap()], data);
+        let options = ReadOptions {
+            raw_data: true,
+            ..ReadOptions::default()
+        };
+        let mut file = from_str_with_options(&text, &options).unwrap();
+        assert_eq!(file.measurements[0].raw_data[1], vec!["1E-3", "+1.1", "2.10"]);
+        assert!(to_string(&file).unwrap().ends_with(data));
+        assert_eq!(from_str(&text).unwrap().measurements[0].raw_data, Vec::<Vec<String>>::new());
+
+        // A changed value is formatted anew
+        file.measurements[0].data[0].0[1] = 1.5;
+        assert!(to_string(&file).unwrap().contains("\n0.000\t1.5\t2.000\n"));
+
+        let columns = text.replace(
+            data,
+            "0.000\n1E-3\n0.002\n1.10\n+1.1\n1.20\n2.000\n2.10\n2.2\n",
+        );
+        let options = ReadOptions {
+            column_major: true,
+            ..options
+        };
+        let file = from_str_with_options(&columns, &options).unwrap();
+        assert!(to_string(&file).unwrap().ends_with(data));
+    }
+
+    #[test]
+    fn fixed_precision() {
+        let file = from_str(FILE).unwrap();
+        let options = WriteOptions {
+            precision: Precision::Decimals(2),
+            column_precision: vec![Precision::Decimals(3)],
+            ..WriteOptions::default()
+        };
+        let text = to_string_with_options(&file, &options).unwrap();
+        assert!(text.ends_with(
+            "0.000\t1.00\t2.00\n\
+             0.001\t1.10\t2.10\n\
+             0.002\t1.20\t2.20\n"
+        ));
+        assert_eq!(from_str(&text).unwrap(), file);
+
+        let significant = Precision::Significant(3);
+        assert_eq!(significant.format(0.0123456), Some("0.0123".to_string()));
+        assert_eq!(significant.format(-1.0), Some("-1.00".to_string()));
+        assert_eq!(significant.format(0.0), Some("0.00".to_string()));
+        assert_eq!(Precision::Significant(2).format(12345.0), Some("12000".to_string()));
+        assert_eq!(significant.format(f64::NAN), None);
+        assert_eq!(Precision::Shortest.format(0.5), None);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn lossy_rows() {
+        let data = "0.000\t1.0\t2.0\n\
+                    0.001\tone\t2.1\n\
+                    0.002\t1.2\t2.2\n\
+                    0.003\t\t2.3\n\
+                    0.004\t1.4\t2.4\n";
+        let text = format!("{}{}", &FILE[..FILE.find("0.000").unwrap()], data);
+        assert_eq!(from_str(&text).unwrap_err().line(), Some(24));
+
+        let (file, diagnostics) = from_reader_lossy(text.as_bytes()).unwrap();
+        let rows: Vec<&[lvm::Sample]> =
+            file.measurements[0].data.iter().map(|row| &row.0[..]).collect();
+        assert_eq!(rows, vec![&[0.0, 1.0, 2.0][..], &[0.002, 1.2, 2.2], &[0.004, 1.4, 2.4]]);
+        let lines: Vec<usize> = diagnostics.iter().map(|&(line, _)| line).collect();
+        assert_eq!(lines, vec![24, 26]);
+        assert_eq!(diagnostics[0].1.line(), Some(24));
+
+        let broken_header = text.replace("Samples\t3\t3", "Samples\tthree\t3");
+        assert!(from_reader_lossy(broken_header.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn reversed_header_layout() {
+        let keys = ["Writer_Version", "Separator", "X_Columns", "Date", "Samples", "Delta_X"];
+        let text: String = FILE
+            .lines()
+            .map(|line| {
+                let mut fields: Vec<&str> = line.split('\t').collect();
+                if keys.contains(&fields[0]) {
+                    let key = fields.remove(0);
+                    let values = fields.iter().rposition(|field| !field.is_empty()).unwrap();
+                    fields.insert(values + 1, key);
+                }
+                fields.join("\t") + "\n"
+            })
+            .collect();
+        assert!(text.contains("\n2\tWriter_Version\n"));
+        assert!(text.contains("\n3\t3\tSamples\t\n"));
+        assert!(from_str(&text).is_err());
+
+        let options = ReadOptions {
+            lenient: true,
+            ..ReadOptions::default()
+        };
+        let file = from_str_with_options(&text, &options).unwrap();
+        assert_eq!(file, from_str(FILE).unwrap());
+    }
+
+    #[test]
+    fn padded_end_of_header() {
+        let text = FILE
+            .replacen("***End_of_Header***", "***End_of_Header***  ", 1)
+            .replace("***End_of_Header***\t\t", " ***END_OF_HEADER***\t\t");
+        assert!(from_str(&text).is_err());
+
+        let options = ReadOptions {
+            lenient: true,
+            ..ReadOptions::default()
+        };
+        let file = from_str_with_options(&text, &options).unwrap();
+        assert_eq!(file, from_str(FILE).unwrap());
+    }
+
+    #[test]
+    fn header_terminator_always_written() {
+        let mut file = from_str_with_options(
+            FILE,
+            &ReadOptions {
+                raw_header: true,
+                ..ReadOptions::default()
+            },
+        ).unwrap();
+        let write_options = WriteOptions {
+            raw_header: true,
+            ..WriteOptions::default()
+        };
+        let written = to_string_with_options(&file, &write_options).unwrap();
+        assert_eq!(written.matches("***End_of_Header***").count(), 2);
+        assert!(written.ends_with('\n'));
+
+        // A raw header edited without its terminator, or its final newline
+        for raw_header in &["LabVIEW Measurement\t\nDate\t2018/02/03\n", "LabVIEW Measurement\t"] {
+            file.raw_header = Some(raw_header.to_string());
+            let written = to_string_with_options(&file, &write_options).unwrap();
+            assert_eq!(written.matches("***End_of_Header***").count(), 2);
+            assert!(written.contains("\n***End_of_Header***\t\n\t\nChannels"));
+        }
+
+        file.measurements.clear();
+        let written = to_string(&file).unwrap();
+        assert!(written.contains("\n***End_of_Header***\t\n"));
+        assert!(written.ends_with('\n'));
+        assert_eq!(from_str(&written).unwrap().header, file.header);
+    }
 }
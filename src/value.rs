@@ -0,0 +1,474 @@
+use super::errors::*;
+use lvm;
+use serde;
+use std;
+
+/// A dynamically typed value, as produced by `to_value`
+///
+/// Structures such as headers become objects keyed by their field names as written in LVM
+/// files, and sequences such as data rows become arrays.
+#[derive(Clone, Debug, PartialEq)]
+#[must_use]
+pub enum Value {
+    /// A missing value
+    Null,
+    /// A boolean
+    Bool(bool),
+    /// A negative integer
+    Integer(i64),
+    /// A non-negative integer
+    Unsigned(u64),
+    /// A floating-point number
+    Float(f64),
+    /// A string, also used for dates, times, versions and enumerations
+    String(String),
+    /// An ordered sequence of values
+    Array(Vec<Value>),
+    /// Named values, in the order they were given
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Returns the value with the specified key, if this is an object holding one
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match *self {
+            Value::Object(ref entries) => entries
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+impl serde::ser::Serialize for Value {
+    fn serialize<S: serde::ser::Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::{SerializeMap, SerializeSeq};
+        match *self {
+            Value::Null => s.serialize_none(),
+            Value::Bool(v) => s.serialize_bool(v),
+            Value::Integer(v) => s.serialize_i64(v),
+            Value::Unsigned(v) => s.serialize_u64(v),
+            Value::Float(v) => s.serialize_f64(v),
+            Value::String(ref v) => s.serialize_str(v),
+            Value::Array(ref values) => {
+                let mut seq = s.serialize_seq(Some(values.len()))?;
+                for value in values {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+            Value::Object(ref entries) => {
+                let mut map = s.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+/// Converts parsed LVM data into a dynamically typed tree
+///
+/// The tree holds the file's header and measurements as objects, with data rows as arrays,
+/// so it can be inspected or handed to another serde format without a fixed structure.
+pub fn to_value(i_file: &lvm::File) -> Result<Value> {
+    serialize_value(i_file)
+}
+
+fn serialize_value<T: serde::ser::Serialize + ?Sized>(i_value: &T) -> Result<Value> {
+    i_value.serialize(ValueSerializer)
+}
+
+#[cfg(feature = "serde_json")]
+impl lvm::File {
+    /// Formats the file as a JSON document
+    ///
+    /// The document holds the file header and a list of measurements, each with its header,
+    /// data headings and data as an array of rows. Comments of data rows are listed by row
+    /// index when there are any, and NaN values become `null`.
+    pub fn to_json(&self) -> String {
+        let measurements = self.measurements.iter().map(|measurement| {
+            let rows = measurement.data.iter().map(|(values, _)| values);
+            let mut entries = vec![
+                ("header", serialize_value(&measurement.header)),
+                ("data_headings", serialize_value(&measurement.data_headings)),
+                ("data", serialize_value(&rows.collect::<Vec<_>>())),
+            ];
+            let comments: Vec<_> = measurement
+                .data
+                .iter()
+                .enumerate()
+                .filter_map(|(row, (_, comment))| comment.as_ref().map(|c| (row, c)))
+                .collect();
+            if !comments.is_empty() {
+                entries.push(("comments", serialize_value(&comments)));
+            }
+            object(entries)
+        });
+        let mut entries = vec![
+            ("header", serialize_value(&self.header)),
+            ("measurements", measurements.collect::<Result<_>>().map(Value::Array)),
+        ];
+        if !self.comments.is_empty() {
+            entries.push(("comments", serialize_value(&self.comments)));
+        }
+        let value = object(entries).expect("parsed LVM data holds no out of range integers");
+        serde_json::to_string(&value).expect("a Value always converts to JSON")
+    }
+}
+
+#[cfg(feature = "serde_json")]
+fn object<I: IntoIterator<Item = (&'static str, Result<Value>)>>(i_entries: I) -> Result<Value> {
+    let entries = i_entries
+        .into_iter()
+        .map(|(key, value)| value.map(|value| (key.to_string(), value)))
+        .collect::<Result<_>>()?;
+    Ok(Value::Object(entries))
+}
+
+/// Serializer building a `Value`
+#[derive(Clone, Copy, Debug)]
+struct ValueSerializer;
+
+/// Wraps the value of an enumeration variant in an object keyed by the variant
+fn variant(i_variant: Option<&'static str>, i_value: Value) -> Value {
+    match i_variant {
+        Some(variant) => Value::Object(vec![(variant.to_string(), i_value)]),
+        None => i_value,
+    }
+}
+
+#[must_use]
+struct ArrayBuilder {
+    values: Vec<Value>,
+    variant: Option<&'static str>,
+}
+
+impl ArrayBuilder {
+    fn push<T: serde::ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.values.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn build(self) -> Value {
+        variant(self.variant, Value::Array(self.values))
+    }
+}
+
+impl serde::ser::SerializeSeq for ArrayBuilder {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: serde::ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(self.build())
+    }
+}
+
+impl serde::ser::SerializeTuple for ArrayBuilder {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: serde::ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(self.build())
+    }
+}
+
+impl serde::ser::SerializeTupleStruct for ArrayBuilder {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: serde::ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(self.build())
+    }
+}
+
+impl serde::ser::SerializeTupleVariant for ArrayBuilder {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: serde::ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(self.build())
+    }
+}
+
+#[must_use]
+struct ObjectBuilder {
+    entries: Vec<(String, Value)>,
+    key: Option<String>,
+    variant: Option<&'static str>,
+}
+
+impl ObjectBuilder {
+    fn insert<T: serde::ser::Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<()> {
+        self.entries.push((key, value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+
+    fn build(self) -> Value {
+        variant(self.variant, Value::Object(self.entries))
+    }
+}
+
+impl serde::ser::SerializeMap for ObjectBuilder {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T: serde::ser::Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        match key.serialize(ValueSerializer)? {
+            Value::String(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            key => Err(serde::ser::Error::custom(format!("{:?} is not a string key", key))),
+        }
+    }
+
+    fn serialize_value<T: serde::ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self.key.take().unwrap_or_default();
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(self.build())
+    }
+}
+
+impl serde::ser::SerializeStruct for ObjectBuilder {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: serde::ser::Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(self.build())
+    }
+}
+
+impl serde::ser::SerializeStructVariant for ObjectBuilder {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: serde::ser::Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(self.build())
+    }
+}
+
+impl ValueSerializer {
+    fn array(i_len: Option<usize>, i_variant: Option<&'static str>) -> ArrayBuilder {
+        ArrayBuilder {
+            values: Vec::with_capacity(i_len.unwrap_or(0)),
+            variant: i_variant,
+        }
+    }
+
+    fn object(i_variant: Option<&'static str>) -> ObjectBuilder {
+        ObjectBuilder {
+            entries: vec![],
+            key: None,
+            variant: i_variant,
+        }
+    }
+
+    fn integer<T>(i_value: T) -> Result<Value>
+    where
+        T: Copy + std::fmt::Display + std::convert::TryInto<u64> + std::convert::TryInto<i64>,
+    {
+        if let Ok(value) = std::convert::TryInto::<u64>::try_into(i_value) {
+            return Ok(Value::Unsigned(value));
+        }
+        std::convert::TryInto::<i64>::try_into(i_value)
+            .map(Value::Integer)
+            .map_err(|_| serde::ser::Error::custom(format!("{} is out of range", i_value)))
+    }
+}
+
+impl serde::ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = ArrayBuilder;
+    type SerializeTuple = ArrayBuilder;
+    type SerializeTupleStruct = ArrayBuilder;
+    type SerializeTupleVariant = ArrayBuilder;
+    type SerializeMap = ObjectBuilder;
+    type SerializeStruct = ObjectBuilder;
+    type SerializeStructVariant = ObjectBuilder;
+
+    fn serialize_bool(self, v: bool) -> Result<Value> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value> {
+        Self::integer(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value> {
+        Self::integer(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value> {
+        Self::integer(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value> {
+        Self::integer(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value> {
+        Self::integer(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value> {
+        Self::integer(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value> {
+        Self::integer(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value> {
+        Self::integer(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value> {
+        Self::integer(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value> {
+        Self::integer(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value> {
+        Ok(Value::Float(lvm::widen(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value> {
+        Ok(Value::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value> {
+        Ok(Value::Array(v.iter().map(|&b| Value::Unsigned(b.into())).collect()))
+    }
+
+    fn serialize_none(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: serde::ser::Serialize + ?Sized>(self, v: &T) -> Result<Value> {
+        v.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value> {
+        Ok(Value::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: serde::ser::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        v: &T,
+    ) -> Result<Value> {
+        v.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: serde::ser::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        v: &T,
+    ) -> Result<Value> {
+        Ok(self::variant(Some(variant), v.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ArrayBuilder> {
+        Ok(Self::array(len, None))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ArrayBuilder> {
+        Ok(Self::array(Some(len), None))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<ArrayBuilder> {
+        Ok(Self::array(Some(len), None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<ArrayBuilder> {
+        Ok(Self::array(Some(len), Some(variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<ObjectBuilder> {
+        Ok(Self::object(None))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<ObjectBuilder> {
+        Ok(Self::object(None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<ObjectBuilder> {
+        Ok(Self::object(Some(variant)))
+    }
+}